@@ -0,0 +1,207 @@
+// ordered, multi-valued container for the attributes of the 9th column of GTF/GFF records
+// keys keep their case and order of appearance, repeated keys (tag "basic"; tag "CCDS";) keep every value
+
+use crate::utils::percent_encode;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Attribute {
+    key: String,
+    value: String,
+    quoted: bool, // GTF only - whether the value was enclosed in double quotes (level 2; vs gene_id "g1";)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Attributes {
+    entries: Vec<Attribute>,
+}
+
+impl Attributes {
+    pub fn new() -> Self {
+        Attributes { entries: Vec::new() }
+    }
+
+    // number of key/value pairs, counting repeated keys once per value
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // first value of the key
+    pub fn get(&self, key: &str) -> Option<&String> {
+        self.entries.iter().find(|a| a.key == key).map(|a| &a.value)
+    }
+
+    // all values of the key in order of appearance
+    pub fn get_all<'a>(&'a self, key: &'a str) -> impl Iterator<Item = &'a String> + 'a {
+        self.entries.iter().filter(move |a| a.key == key).map(|a| &a.value)
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.entries.iter().any(|a| a.key == key)
+    }
+
+    // true if the key/value pair is present (among possibly several values of the key)
+    pub fn contains(&self, key: &str, value: &str) -> bool {
+        self.entries.iter().any(|a| a.key == key && a.value == value)
+    }
+
+    // set the value of the key, replacing all existing values
+    // the key keeps the position of its first occurrence, new keys are appended
+    pub fn insert(&mut self, key: &str, value: String) {
+        match self.entries.iter().position(|a| a.key == key) {
+            Some(pos) => {
+                self.entries[pos].value = value;
+                let mut idx = 0;
+                self.entries.retain(|a| {
+                    idx += 1;
+                    idx - 1 <= pos || a.key != key
+                });
+            }
+            None => self.push(key, value),
+        }
+    }
+
+    // append a value without touching existing values of the key
+    pub fn push(&mut self, key: &str, value: String) {
+        self.entries.push(Attribute { key: key.to_string(), value, quoted: true });
+    }
+
+    // append a value written without quotes in GTF (e.g. level 2;)
+    pub fn push_unquoted(&mut self, key: &str, value: String) {
+        self.entries.push(Attribute { key: key.to_string(), value, quoted: false });
+    }
+
+    // remove all values of the key and return them
+    pub fn remove(&mut self, key: &str) -> Vec<String> {
+        let (removed, kept) = std::mem::take(&mut self.entries).into_iter().partition(|a| a.key == key);
+        self.entries = kept;
+        removed.into_iter().map(|a: Attribute| a.value).collect()
+    }
+
+    // mutable access to all values of the key (e.g. to rename IDs in place)
+    pub fn values_mut<'a>(&'a mut self, key: &'a str) -> impl Iterator<Item = &'a mut String> + 'a {
+        self.entries.iter_mut().filter(move |a| a.key == key).map(|a| &mut a.value)
+    }
+
+    // write all values of the key with or without quotes in GTF
+    pub fn set_quoted(&mut self, key: &str, quoted: bool) {
+        for a in self.entries.iter_mut().filter(|a| a.key == key) {
+            a.quoted = quoted;
+        }
+    }
+
+    // distinct keys with at least one value written without quotes in GTF
+    pub fn unquoted_keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = Vec::new();
+        for a in self.entries.iter().filter(|a| !a.quoted) {
+            if !keys.contains(&a.key.as_str()) {
+                keys.push(&a.key);
+            }
+        }
+        keys
+    }
+
+    pub fn retain<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&str, &str) -> bool,
+    {
+        self.entries.retain(|a| f(&a.key, &a.value));
+    }
+
+    // move all values of the given keys to the front, in the order of the keys
+    // the remaining attributes keep their order
+    pub fn move_to_front(&mut self, keys: &[&str]) {
+        let mut entries = std::mem::take(&mut self.entries);
+        for key in keys {
+            let (front, rest): (Vec<Attribute>, Vec<Attribute>) = entries.into_iter().partition(|a| a.key == *key);
+            self.entries.extend(front);
+            entries = rest;
+        }
+        self.entries.extend(entries);
+    }
+
+    // key/value pairs in order of appearance
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries.iter().map(|a| (a.key.as_str(), a.value.as_str()))
+    }
+
+    // distinct keys in order of first appearance
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = Vec::new();
+        for a in &self.entries {
+            if !keys.contains(&a.key.as_str()) {
+                keys.push(&a.key);
+            }
+        }
+        keys
+    }
+
+    // attribute column in GTF syntax
+    pub fn gtf(&self) -> String {
+        self.entries
+            .iter()
+            .map(|a| match a.quoted {
+                true => format!("{} \"{}\";", a.key, a.value),
+                false => format!("{} {};", a.key, a.value),
+            })
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    // attribute column in GFF3 syntax
+    // repeated keys are written once with comma-separated values (Parent=tx1,tx2) and reserved characters are escaped
+    pub fn gff(&self) -> String {
+        self.keys()
+            .iter()
+            .map(|key| {
+                let values = self.get_all(key).map(|v| percent_encode(v)).collect::<Vec<String>>();
+                format!("{}={}", key, values.join(","))
+            })
+            .collect::<Vec<String>>()
+            .join(";")
+    }
+}
+
+impl<'a> IntoIterator for &'a Attributes {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Box<dyn Iterator<Item = (&'a str, &'a str)> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_attributes() {
+        let mut attrs = Attributes::new();
+        attrs.push("gene_id", "g1".to_string());
+        attrs.push("tag", "basic".to_string());
+        attrs.push_unquoted("level", "2".to_string());
+        attrs.push("tag", "CCDS".to_string());
+
+        assert_eq!(attrs.len(), 4);
+        assert_eq!(attrs.get("tag").unwrap(), "basic");
+        assert_eq!(attrs.get_all("tag").collect::<Vec<_>>(), vec!["basic", "CCDS"]);
+        assert!(attrs.contains("tag", "CCDS"));
+        assert_eq!(attrs.keys(), vec!["gene_id", "tag", "level"]);
+        assert_eq!(attrs.gtf(), "gene_id \"g1\"; tag \"basic\"; level 2; tag \"CCDS\";");
+
+        attrs.insert("tag", "mane".to_string());
+        assert_eq!(attrs.gtf(), "gene_id \"g1\"; tag \"mane\"; level 2;");
+        assert_eq!(attrs.unquoted_keys(), vec!["level"]);
+        attrs.set_quoted("level", true);
+        attrs.set_quoted("tag", false);
+        assert_eq!(attrs.gtf(), "gene_id \"g1\"; tag mane; level \"2\";");
+        attrs.set_quoted("tag", true);
+        attrs.insert("gene_name", "G1".to_string());
+        assert_eq!(attrs.remove("gene_id"), vec!["g1"]);
+        assert_eq!(attrs.iter().collect::<Vec<_>>(), vec![("tag", "mane"), ("level", "2"), ("gene_name", "G1")]);
+    }
+}
@@ -0,0 +1,156 @@
+// error type shared by all public APIs of the library
+
+use std::fmt::{Display, Formatter};
+
+// position of a record in the input
+// file name and line number are empty/0 when the record did not come from a file (e.g. GffObject::new on a string)
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Location {
+    pub fname: String,
+    pub line_num: usize, // 1-based line number
+    pub line: String,    // raw line as read from the input
+}
+
+impl Location {
+    pub fn new(fname: &str, line_num: usize, line: &str) -> Self {
+        Location {
+            fname: fname.to_string(),
+            line_num,
+            line: line.trim_end_matches(['\n', '\r']).to_string(),
+        }
+    }
+
+    pub fn from_line(line: &str) -> Self {
+        Location::new("", 0, line)
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match (self.fname.is_empty(), self.line_num) {
+            (true, 0) => write!(f, "<unknown>"),
+            (true, n) => write!(f, "line {}", n),
+            (false, 0) => write!(f, "{}", self.fname),
+            (false, n) => write!(f, "{}:{}", self.fname, n),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io { fname: String, source: std::io::Error },
+    ColumnCount { loc: Location, found: usize },
+    Coordinate { loc: Location, value: String },
+    Strand { loc: Location, value: String },
+    Score { loc: Location, value: String },
+    Phase { loc: Location, value: String },
+    UnknownFormat { loc: Location, reason: String },
+    DuplicateId { loc: Location, id: String },
+    MissingParent { loc: Location, parent_id: String },
+    Unsorted { loc: Location, previous: String },
+    InvalidObject { oid: usize, reason: String },
+}
+
+impl Error {
+    pub fn io(fname: &str, source: std::io::Error) -> Self {
+        Error::Io { fname: fname.to_string(), source }
+    }
+
+    // location of the offending record if the error refers to one
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::ColumnCount { loc, .. }
+            | Error::Coordinate { loc, .. }
+            | Error::Strand { loc, .. }
+            | Error::Score { loc, .. }
+            | Error::Phase { loc, .. }
+            | Error::UnknownFormat { loc, .. }
+            | Error::DuplicateId { loc, .. }
+            | Error::MissingParent { loc, .. }
+            | Error::Unsorted { loc, .. } => Some(loc),
+            Error::Io { .. } | Error::InvalidObject { .. } => None,
+        }
+    }
+
+    // set file name and line number of the record the error refers to
+    // used by readers to annotate errors raised while parsing a single line
+    pub fn at(mut self, fname: &str, line_num: usize) -> Self {
+        match &mut self {
+            Error::ColumnCount { loc, .. }
+            | Error::Coordinate { loc, .. }
+            | Error::Strand { loc, .. }
+            | Error::Score { loc, .. }
+            | Error::Phase { loc, .. }
+            | Error::UnknownFormat { loc, .. }
+            | Error::DuplicateId { loc, .. }
+            | Error::MissingParent { loc, .. }
+            | Error::Unsorted { loc, .. } => {
+                loc.fname = fname.to_string();
+                loc.line_num = line_num;
+            }
+            Error::Io { fname: io_fname, .. } => {
+                if io_fname.is_empty() {
+                    *io_fname = fname.to_string();
+                }
+            }
+            Error::InvalidObject { .. } => (),
+        }
+        self
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io { fname, source } => write!(f, "{}: {}", fname, source),
+            Error::ColumnCount { loc, found } => {
+                write!(f, "{}: invalid number of columns (expected 9, found {}): {}", loc, found, loc.line)
+            }
+            Error::Coordinate { loc, value } => write!(f, "{}: invalid coordinate '{}': {}", loc, value, loc.line),
+            Error::Strand { loc, value } => write!(f, "{}: invalid strand '{}': {}", loc, value, loc.line),
+            Error::Score { loc, value } => write!(f, "{}: invalid score '{}': {}", loc, value, loc.line),
+            Error::Phase { loc, value } => write!(f, "{}: invalid phase '{}': {}", loc, value, loc.line),
+            Error::UnknownFormat { loc, reason } => write!(f, "{}: cannot determine format: {}", loc, reason),
+            Error::DuplicateId { loc, id } => write!(f, "{}: duplicate ID '{}': {}", loc, id, loc.line),
+            Error::MissingParent { loc, parent_id } => {
+                write!(f, "{}: missing parent '{}': {}", loc, parent_id, loc.line)
+            }
+            Error::Unsorted { loc, previous } => {
+                write!(f, "{}: record is out of order (follows {}): {}", loc, previous, loc.line)
+            }
+            Error::InvalidObject { oid, reason } => write!(f, "object {}: {}", oid, reason),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Self {
+        Error::Io { fname: String::new(), source }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_error_location() {
+        let err = Error::ColumnCount { loc: Location::from_line("chr1\ttest\n"), found: 2 };
+        assert_eq!(err.to_string(), "<unknown>: invalid number of columns (expected 9, found 2): chr1\ttest");
+
+        let err = err.at("test.gtf", 12);
+        let loc = err.location().unwrap();
+        assert_eq!(loc.fname, "test.gtf");
+        assert_eq!(loc.line_num, 12);
+        assert_eq!(err.to_string(), "test.gtf:12: invalid number of columns (expected 9, found 2): chr1\ttest");
+    }
+}
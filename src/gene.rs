@@ -0,0 +1,160 @@
+// read-only view of a gene and its isoforms
+
+use crate::group::GffObjectGroupT;
+use crate::object::GffObjectT;
+use crate::transcript::TranscriptView;
+
+use bio::utils::Interval;
+use bio::data_structures::interval_tree::EntryT;
+
+// genes missing from the input (GTF without gene lines) are synthesized by Transcriptome::finalize
+// so the view works the same with and without explicit gene records once the group is finalized
+pub struct GeneView<'a, Group>
+where
+    Group: GffObjectGroupT,
+{
+    group: &'a Group,
+    gid: usize,
+}
+
+impl<'a, Group> GeneView<'a, Group>
+where
+    Group: GffObjectGroupT,
+{
+    pub fn new(group: &'a Group, gid: usize) -> Self {
+        GeneView { group, gid }
+    }
+
+    pub fn gid(&self) -> usize {
+        self.gid
+    }
+
+    // the gene record itself
+    pub fn object(&self) -> &'a Group::Object {
+        self.group.get(self.gid).unwrap()
+    }
+
+    // isoforms of the gene sorted by position
+    pub fn transcripts(&self) -> Vec<TranscriptView<'a, Group>> {
+        let group = self.group;
+        let mut tids: Vec<usize> = self
+            .object()
+            .children()
+            .iter()
+            .copied()
+            .filter(|tid| group.get(*tid).is_some_and(|t| t.get_type().is_transcript_like()))
+            .collect();
+        tids.sort_by_key(|tid| {
+            let interval = group.get(*tid).unwrap().interval();
+            (interval.start, interval.end)
+        });
+        tids.into_iter().map(|tid| TranscriptView::new(group, tid)).collect()
+    }
+
+    pub fn isoform_count(&self) -> usize {
+        self.transcripts().len()
+    }
+
+    // first to last base covered by any transcript - the gene record itself if it has none
+    pub fn span(&self) -> Interval<usize> {
+        let transcripts = self.transcripts();
+        let start = transcripts.iter().map(|t| t.object().interval().start).min();
+        let end = transcripts.iter().map(|t| t.object().interval().end).max();
+        match (start, end) {
+            (Some(start), Some(end)) => Interval::new(start..end).unwrap(),
+            _ => self.object().interval().clone(),
+        }
+    }
+
+    // distinct exons of all isoforms sorted by position - exons are the same if their coordinates are
+    fn distinct_exons(&self) -> Vec<(usize, usize)> {
+        let mut exons: Vec<(usize, usize)> = self
+            .transcripts()
+            .iter()
+            .flat_map(|t| t.exons())
+            .map(|exon| (exon.interval().start, exon.interval().end))
+            .collect();
+        exons.sort();
+        exons.dedup();
+        exons
+    }
+
+    // number of bases covered by the exons of at least one isoform
+    pub fn exonic_len(&self) -> usize {
+        let mut len = 0;
+        let mut covered_to = 0; // last base counted so far
+        for (start, end) in self.distinct_exons() {
+            if end > covered_to {
+                len += end - start.max(covered_to + 1) + 1;
+                covered_to = end;
+            }
+        }
+        len
+    }
+
+    // exons shared by all isoforms
+    pub fn constitutive_exons(&self) -> Vec<Interval<usize>> {
+        self.exons_by_sharing(true)
+    }
+
+    // exons missing from at least one isoform
+    pub fn alternative_exons(&self) -> Vec<Interval<usize>> {
+        self.exons_by_sharing(false)
+    }
+
+    fn exons_by_sharing(&self, constitutive: bool) -> Vec<Interval<usize>> {
+        let isoform_exons: Vec<Vec<(usize, usize)>> = self
+            .transcripts()
+            .iter()
+            .map(|t| t.exons().iter().map(|exon| (exon.interval().start, exon.interval().end)).collect())
+            .collect();
+        self.distinct_exons()
+            .into_iter()
+            .filter(|exon| isoform_exons.iter().all(|exons| exons.contains(exon)) == constitutive)
+            .map(|(start, end)| Interval::new(start..end).unwrap())
+            .collect()
+    }
+}
+
+impl<'a, Group> std::fmt::Debug for GeneView<'a, Group>
+where
+    Group: GffObjectGroupT,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "GeneView: {{ gid: {} }}", self.gid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::group::Transcriptome;
+
+    #[test]
+    fn test_gene_view() {
+        // no gene line - the gene is synthesized from the transcripts
+        let lines = "chr1\ttest\ttranscript\t100\t600\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t301\t400\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t501\t600\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\ttranscript\t100\t700\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t2\";\n\
+                     chr1\ttest\texon\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t2\";\n\
+                     chr1\ttest\texon\t501\t700\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t2\";\n\
+                     chr1\ttest\ttranscript\t100\t600\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t3\";\n\
+                     chr1\ttest\texon\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t3\";\n\
+                     chr1\ttest\texon\t501\t600\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t3\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+        let oid = |id_str: &str| transcriptome.get_by_id(id_str).unwrap().id.unwrap();
+        assert!(transcriptome.gene(oid("t1")).is_none());
+        let gene = transcriptome.gene(oid("g1")).unwrap();
+
+        assert_eq!(gene.isoform_count(), 3);
+        let transcript_ids: Vec<_> = gene.transcripts().iter().map(|t| t.object().attrs.get("transcript_id").unwrap().clone()).collect();
+        assert_eq!(transcript_ids, vec!["t1", "t3", "t2"]);
+        assert_eq!((gene.span().start, gene.span().end), (100, 700));
+        assert_eq!(gene.exonic_len(), 101 + 100 + 200);
+        let spans = |intervals: Vec<bio::utils::Interval<usize>>| intervals.iter().map(|i| (i.start, i.end)).collect::<Vec<_>>();
+        assert_eq!(spans(gene.constitutive_exons()), vec![(100, 200)]);
+        assert_eq!(spans(gene.alternative_exons()), vec![(301, 400), (501, 600), (501, 700)]);
+    }
+}
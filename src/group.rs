@@ -1,18 +1,21 @@
 // defines traits and srtuct for groups of GffObjects
 
 use std::convert::TryFrom;
-use std::error::Error;
 use std::fs::File;
+use std::io::Read;
 
 use bio::utils::Interval;
 use bio::data_structures::interval_tree::{ArrayBackedIntervalTree, EntryT};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
 use std::cmp::Ordering;
 
+use crate::error::{Error, Location};
 use crate::object::{GffObject, GffObjectT};
-use crate::transcript::TranscriptRef;
-use crate::treader::TReader;
+use crate::gene::GeneView;
+use crate::transcript::{TranscriptRef, TranscriptView};
+use crate::treader::{Comment, TReader};
 use crate::utils::*;
 
 pub trait GffObjectGroupT {
@@ -24,14 +27,79 @@ pub trait GffObjectGroupT {
     fn get_mut(&mut self, oid: usize) -> Option<&mut Self::Object>;
     fn objects(&self) -> &ArrayBackedIntervalTree<Self::Object>;
     fn objects_mut(&mut self) -> &mut ArrayBackedIntervalTree<Self::Object>;
+    fn reset_intervals(&mut self);
+    fn reset_interval(&mut self, oid: usize);
+}
+
+// object whose parent ID does not match any record in the group
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Orphan {
+    pub oid: usize,         // index of the orphaned object in the tree
+    pub parent_id: String,  // parent ID as extracted from the attributes of the object
+}
+
+// how IDs of an input which are already used by an earlier input are handled when loading
+// (two samples assembled by StringTie both containing STRG.1.1, transcript_id "t1" in two annotations)
+// an ID only referenced by an input (Parent=g1) but defined by an earlier one is a link between the inputs, not a collision
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CollisionPolicy {
+    Error,   // refuse to load the input
+    Rename,  // prefix the colliding IDs of the input with the stem of its file name (sample2_STRG.1.1)
+    #[default]
+    Namespace, // keep the IDs - each input resolves its colliding IDs among its own objects only
 }
 
 #[derive(Debug)]
 pub struct Transcriptome {
     objects: ArrayBackedIntervalTree<GffObject>,
-    id_map: HashMap<String, usize>, // map of object IDs to their indices in the tree
+    id_map: HashMap<String, Vec<usize>>, // map of object IDs to their indices in the tree (GTF gene and transcript IDs may coincide)
+    comments: Vec<(usize, Comment)>, // comments and directives with the index of the object they precede in the input
+    inputs: Vec<String>, // names of all inputs added so far - indexed by Origin::input of the objects
+    collision_policy: CollisionPolicy,
+    loaded_ids: HashMap<String, bool>, // IDs used by the inputs loaded so far - true if defined by a record, false if only referenced
+    namespaced: HashSet<String>, // IDs which collided under CollisionPolicy::Namespace
+
+    seq_index: OnceLock<SeqIndex>, // built by the first query - cleared whenever objects were added or could have been modified
+}
+
+// interval trees of the objects on each seqid used by region queries
+#[derive(Debug)]
+struct SeqIndex {
+    trees: HashMap<String, ArrayBackedIntervalTree<IndexEntry>>,
+    ends: HashMap<String, usize>, // largest end coordinate on each seqid - bounds nearest-feature searches
+}
+
+// restrictions of nearest-feature queries
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NearestOptions {
+    pub gtype: Option<Types>,          // only features at the level of this type (Types::Transcript also matches mRNAs)
+    pub max_distance: Option<usize>,   // only features at most this many bases away
+    pub same_strand: bool,             // only features on the strand of the query
+}
+
+// feature found by a nearest-feature query
+// distance is 0 for overlapping features, otherwise the difference between the closest coordinates (1 for adjacent features)
+// positive downstream and negative upstream of the query - relative to its strand, unstranded queries count as +
+#[derive(Clone, Debug)]
+pub struct Neighbor<'a> {
+    pub obj: &'a GffObject,
+    pub distance: i64,
+}
 
-    is_indexed: bool,
+// entry of the per-seqid trees
+// intervals are half-open (end + 1) so that the overlap test of the tree matches the inclusive coordinates of the objects
+#[derive(Clone, Debug)]
+struct IndexEntry {
+    interval: Interval<usize>,
+    oid: usize,
+}
+
+impl EntryT for IndexEntry {
+    type N = usize;
+
+    fn interval(&self) -> &Interval<Self::N> {
+        &self.interval
+    }
 }
 
 impl GffObjectGroupT for Transcriptome {
@@ -41,146 +109,676 @@ impl GffObjectGroupT for Transcriptome {
         Transcriptome {
             objects: ArrayBackedIntervalTree::new(),
             id_map: HashMap::new(),
-            is_indexed: false,
+            comments: Vec::new(),
+            inputs: Vec::new(),
+            collision_policy: CollisionPolicy::default(),
+            loaded_ids: HashMap::new(),
+            namespaced: HashSet::new(),
+            seq_index: OnceLock::new(),
         }
     }
 
     fn add_object(&mut self, obj: Self::Object) -> usize {
-        self.is_indexed = false;
+        self.seq_index.take();
         // after inserting the object - set its ID to the index in the tree
         let oid = self.objects.insert(obj);
         self.objects.get_mut(oid).unwrap().id = Some(oid);
         // add entry to the id_map if id_str is present
         if let Some(id_str) = self.objects.get(oid).unwrap().id_str.clone() {
-            self.id_map.insert(id_str, oid);
+            self.id_map.entry(id_str).or_default().push(oid);
         }
         oid
     }
 
     fn get(&self, oid: usize) -> Option<&Self::Object> {
-        Some(self.objects.get(oid).unwrap())
+        self.objects.get(oid)
     }
 
     fn get_mut(&mut self, oid: usize) -> Option<&mut Self::Object> {
-        Some(self.objects.get_mut(oid).unwrap())
+        self.seq_index.take();
+        self.objects.get_mut(oid)
     }
 
     fn objects(&self) -> &ArrayBackedIntervalTree<Self::Object> {
         &self.objects
     }
     fn objects_mut(&mut self) -> &mut ArrayBackedIntervalTree<Self::Object> {
+        self.seq_index.take();
         &mut self.objects
     }
+
+    fn reset_intervals(&mut self) {
+        // objects themselves have no control over the intervals of their children, since children are stored as indices only
+        // after children have been added to an object
+        // we need to make sure parents have intervals that cover all of their children end-to-end
+
+        // starting from the top-level objects, set the interval of each object to min(start), max(end) of its children
+        // children are processed before their parents, so the change propagates up to genes
+        // intervals changed - the index is rebuilt on the next query
+        let roots: Vec<usize> = (0..self.objects.len())
+            .filter(|oid| self.objects.get(*oid).unwrap().parents.is_empty())
+            .collect();
+        for oid in roots {
+            self.reset_subtree(oid);
+        }
+        self.seq_index.take();
+    }
+
+    fn reset_interval(&mut self, oid: usize) {
+        // same as reset_intervals but only for a single object whose children were modified
+        // and all of its ancestors
+        let mut stack = vec![oid];
+        while let Some(oid) = stack.pop() {
+            if let Some((start, end)) = self.span_of_children(oid) {
+                self.objects.get_mut(oid).unwrap().interval = Interval::new(start..end).unwrap();
+            }
+            stack.extend_from_slice(self.objects.get(oid).unwrap().parents());
+        }
+        self.seq_index.take();
+    }
 }
 
 impl Transcriptome {
-    pub fn from_file(fname: &str) -> Result<Transcriptome, Box<dyn Error>> {
+    pub fn from_file(fname: &str) -> Result<Transcriptome, Error> {
         let mut transcriptome = Transcriptome::new();
+        transcriptome.add_from_file(fname)?;
+        Ok(transcriptome)
+    }
 
-        let mut reader = TReader::new(Some(fname))?;
-        while let Some(obj) = reader.next() {
-            transcriptome.add_object(obj);
-        }
-        transcriptome.is_indexed = false;
+    pub fn from_reader<R: Read>(reader: R) -> Result<Transcriptome, Error> {
+        let mut transcriptome = Transcriptome::new();
+        transcriptome.add_from_reader(reader)?;
         Ok(transcriptome)
     }
 
-    pub fn add_from_file(&mut self, fname: &str) -> Result<(), Box<dyn Error>> {
+    pub fn add_from_file(&mut self, fname: &str) -> Result<(), Error> {
         let mut reader = TReader::new(Some(fname))?;
+        self.add_from_treader(&mut reader)
+    }
+
+    pub fn add_from_reader<R: Read>(&mut self, reader: R) -> Result<(), Error> {
+        let mut reader = TReader::from_reader(reader)?;
+        self.add_from_treader(&mut reader)
+    }
+
+    // add all records of a reader
+    // the reader can be configured beforehand (e.g. with a ParsePolicy) and its statistics inspected afterwards
+    pub fn add_from_treader(&mut self, reader: &mut TReader) -> Result<(), Error> {
+        // comments read by the reader before a record are attached to the object created from that record
+        // inputs are numbered across all readers added to the group
+        // records are collected first so that IDs colliding with earlier inputs are handled before anything is added
+        let first_input = self.inputs.len();
+        let mut objs: Vec<GffObject> = Vec::new();
+        let mut comments: Vec<(usize, Comment)> = Vec::new();
         while let Some(obj) = reader.next() {
+            let mut obj = obj?;
+            if let Some(origin) = obj.origin.as_mut() {
+                origin.input += first_input;
+            }
+            comments.extend(reader.take_comments().into_iter().map(|c| (objs.len(), c)));
+            objs.push(obj);
+        }
+        comments.extend(reader.take_comments().into_iter().map(|c| (objs.len(), c)));
+
+        for (i, fname) in reader.fnames().iter().enumerate() {
+            self.handle_collisions(&mut objs, first_input + i, fname)?;
+        }
+
+        self.inputs.extend(reader.fnames().iter().cloned());
+        let first_oid = self.objects.len();
+        self.comments.extend(comments.into_iter().map(|(i, c)| (first_oid + i, c)));
+        for obj in objs {
             self.add_object(obj);
         }
-        self.is_indexed = false;
+        self.seq_index.take();
+        Ok(())
+    }
+
+    // first object with the given ID (ID= in GFF, gene_id/transcript_id of gene and transcript records in GTF)
+    // GTF genes and transcripts can share an ID, and namespaced inputs can repeat it - see oids_by_id for all of them
+    pub fn get_by_id(&self, id_str: &str) -> Option<&GffObject> {
+        self.id_map.get(id_str)?.first().and_then(|oid| self.objects.get(*oid))
+    }
+
+    // all objects with the given attribute value (e.g. gene_name "HMGN1") in the order they were added
+    pub fn get_by_attr(&self, key: &str, value: &str) -> Vec<&GffObject> {
+        (0..self.objects.len())
+            .filter_map(|oid| self.objects.get(oid))
+            .filter(|obj| obj.attrs.contains(key, value))
+            .collect()
+    }
+
+    // objects with the given ID - restricted to a single input to tell apart copies of a namespaced ID
+    pub fn oids_by_id(&self, id_str: &str, input: Option<usize>) -> Vec<usize> {
+        self.id_map
+            .get(id_str)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|oid| input.is_none() || self.objects.get(*oid).unwrap().origin.as_ref().map(|origin| origin.input) == input)
+            .collect()
+    }
+
+    pub fn set_collision_policy(&mut self, policy: CollisionPolicy) {
+        self.collision_policy = policy;
+    }
+
+    pub fn collision_policy(&self) -> CollisionPolicy {
+        self.collision_policy
+    }
+
+    // apply the collision policy to the objects of one input and register the IDs they use
+    fn handle_collisions(&mut self, objs: &mut [GffObject], input: usize, fname: &str) -> Result<(), Error> {
+        let from_input = |obj: &GffObject| obj.origin.as_ref().map(|origin| origin.input) == Some(input);
+        // IDs referenced by a record - the gene_id of GTF exons becomes the parent of their synthesized transcript
+        let refs_of = |obj: &GffObject| {
+            let gene_id = match obj.is_gff() || obj.g_type.is_gene_like() {
+                true => None,
+                false => obj.attrs.get("gene_id").cloned(),
+            };
+            obj.parent_id_strs.iter().cloned().chain(gene_id).collect::<Vec<String>>()
+        };
+        let ids_of = |obj: &GffObject| obj.id_str.clone().into_iter().chain(refs_of(obj)).collect::<Vec<String>>();
+
+        // IDs used by the input - true if defined by one of its records
+        let mut used: HashMap<String, bool> = HashMap::new();
+        for obj in objs.iter().filter(|obj| from_input(obj)) {
+            for parent_id_str in refs_of(obj) {
+                used.entry(parent_id_str).or_insert(false);
+            }
+            if let Some(id_str) = &obj.id_str {
+                used.insert(id_str.clone(), true);
+            }
+        }
+        let colliding: HashSet<String> = used
+            .iter()
+            .filter(|(id, defined)| self.loaded_ids.get(*id).is_some_and(|loaded_defined| **defined || !loaded_defined))
+            .map(|(id, _)| id.clone())
+            .collect();
+
+        if !colliding.is_empty() {
+            match self.collision_policy {
+                CollisionPolicy::Error => {
+                    let obj = objs.iter()
+                        .filter(|obj| from_input(obj))
+                        .find(|obj| ids_of(obj).iter().any(|id| colliding.contains(id)))
+                        .unwrap();
+                    let id = ids_of(obj).into_iter().find(|id| colliding.contains(id)).unwrap();
+                    let line = if obj.is_gff() { obj.gff() } else { obj.gtf() };
+                    let line_num = obj.origin.as_ref().map_or(0, |origin| origin.line_num);
+                    return Err(Error::DuplicateId { loc: Location::new(fname, line_num, &line), id });
+                }
+                CollisionPolicy::Rename => {
+                    // the prefix must not produce an ID already loaded or used by the input (inputs with the same file name)
+                    let stem = file_stem(fname);
+                    let prefix = (1..)
+                        .map(|n| match n {
+                            1 => format!("{}_", stem),
+                            _ => format!("{}{}_", stem, n),
+                        })
+                        .find(|prefix| {
+                            colliding.iter().all(|id| {
+                                let renamed = format!("{}{}", prefix, id);
+                                !self.loaded_ids.contains_key(&renamed) && !used.contains_key(&renamed)
+                            })
+                        })
+                        .unwrap();
+                    for obj in objs.iter_mut().filter(|obj| from_input(obj)) {
+                        rename_ids(obj, &colliding, &prefix);
+                    }
+                    used = used
+                        .into_iter()
+                        .map(|(id, defined)| match colliding.contains(&id) {
+                            true => (format!("{}{}", prefix, id), defined),
+                            false => (id, defined),
+                        })
+                        .collect();
+                }
+                CollisionPolicy::Namespace => {
+                    self.namespaced.extend(colliding);
+                }
+            }
+        }
+
+        for (id, defined) in used {
+            *self.loaded_ids.entry(id).or_insert(false) |= defined;
+        }
         Ok(())
     }
 
-    fn index(&mut self) {
+    // names of all inputs in the order they were added
+    pub fn inputs(&self) -> &[String] {
+        &self.inputs
+    }
+
+    // objects read from (or synthesized for records of) the given input
+    pub fn objects_from(&self, input: usize) -> impl Iterator<Item = &GffObject> {
+        (0..self.objects.len())
+            .filter_map(move |oid| self.objects.get(oid))
+            .filter(move |obj| obj.origin.as_ref().map(|origin| origin.input) == Some(input))
+    }
+
+    // store the name of the input of each object as an attribute so that it is written out with the object
+    // (e.g. label_origin("source_file") -> source_file "sample1.gtf";)
+    pub fn label_origin(&mut self, key: &str) {
+        for oid in 0..self.objects.len() {
+            let obj = self.objects.get_mut(oid).unwrap();
+            if let Some(origin) = obj.origin.clone() {
+                obj.attrs.insert(key, origin.fname.to_string());
+            }
+        }
+    }
+
+    // comments and directives in input order, each with the index of the object it preceded
+    // comments at the end of an input precede the first object of the next input
+    pub fn comments(&self) -> &[(usize, Comment)] {
+        &self.comments
+    }
+
+    pub fn comments_before(&self, oid: usize) -> impl Iterator<Item = &Comment> {
+        self.comments.iter().filter(move |(before, _)| *before == oid).map(|(_, c)| c)
+    }
+
+    pub fn add_comment(&mut self, before: usize, comment: Comment) {
+        self.comments.push((before, comment));
+    }
+
+    // values of all directives with the given name (e.g. "sequence-region" -> ["chr1 1 1000", ...])
+    pub fn directives(&self, name: &str) -> Vec<&str> {
+        self.comments
+            .iter()
+            .filter_map(|(_, c)| c.directive())
+            .filter(|(n, _)| *n == name)
+            .map(|(_, v)| v)
+            .collect()
+    }
+
+    pub fn index(&mut self) {
         // index the tree
-        // set is_indexed to true
+        // and build the per-seqid trees used by region queries now instead of on the first query
         self.objects.index();
-        self.is_indexed = true;
+        self.seq_index();
+    }
+
+    fn seq_index(&self) -> &SeqIndex {
+        self.seq_index.get_or_init(|| {
+            let mut index = SeqIndex { trees: HashMap::new(), ends: HashMap::new() };
+            for oid in 0..self.objects.len() {
+                let obj = self.objects.get(oid).unwrap();
+                let seq_end = index.ends.entry(obj.seqid.clone()).or_insert(0);
+                *seq_end = (*seq_end).max(obj.interval.end);
+                let interval = Interval::new(obj.interval.start..obj.interval.end + 1).unwrap();
+                index.trees
+                    .entry(obj.seqid.clone())
+                    .or_insert_with(ArrayBackedIntervalTree::new)
+                    .insert(IndexEntry { interval, oid });
+            }
+            for tree in index.trees.values_mut() {
+                tree.index();
+            }
+            index
+        })
+    }
+
+    // closest feature to the query - the query itself and its parents and children are never reported
+    pub fn nearest(&self, query: &GffObject, opts: &NearestOptions) -> Option<Neighbor<'_>> {
+        self.k_nearest(query, 1, opts).into_iter().next()
+    }
+
+    // k closest features sorted by absolute distance - ties go to the upstream feature
+    pub fn k_nearest(&self, query: &GffObject, k: usize, opts: &NearestOptions) -> Vec<Neighbor<'_>> {
+        self.find_nearest(query, k, opts, |_| true)
+    }
+
+    // closest non-overlapping features upstream and downstream of the query (e.g. the genes flanking a novel transcript)
+    pub fn flanking(&self, query: &GffObject, opts: &NearestOptions) -> (Option<Neighbor<'_>>, Option<Neighbor<'_>>) {
+        let upstream = self.find_nearest(query, 1, opts, |distance| distance < 0).into_iter().next();
+        let downstream = self.find_nearest(query, 1, opts, |distance| distance > 0).into_iter().next();
+        (upstream, downstream)
     }
 
-    pub fn create_parent(&mut self, obj: &GffObject) -> Result<usize, Box<dyn Error>> {
+    fn find_nearest<F>(&self, query: &GffObject, k: usize, opts: &NearestOptions, accept: F) -> Vec<Neighbor<'_>>
+    where
+        F: Fn(i64) -> bool,
+    {
+        let index = self.seq_index();
+        let (tree, seq_end) = match (index.trees.get(&query.seqid), index.ends.get(&query.seqid)) {
+            (Some(tree), Some(seq_end)) => (tree, *seq_end),
+            _ => return Vec::new(),
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+        let excluded = self.relatives(query);
+        let (start, end) = (query.interval.start, query.interval.end);
+        let signed = |obj: &GffObject| -> i64 {
+            let distance = if obj.interval.start > end {
+                (obj.interval.start - end) as i64
+            } else if obj.interval.end < start {
+                -((start - obj.interval.end) as i64)
+            } else {
+                0
+            };
+            if query.strand == '-' { -distance } else { distance }
+        };
+
+        // search windows of growing size around the query until k features are found within the window
+        // features outside of a window are further away than any feature found inside it
+        let max_radius = opts.max_distance.unwrap_or(seq_end.max(end));
+        let mut radius = max_radius.min(1000);
+        loop {
+            let mut hits: Vec<Neighbor> = tree
+                .find(start.saturating_sub(radius)..end + radius + 1)
+                .into_iter()
+                .filter(|entry| !excluded.contains(&entry.oid))
+                .map(|entry| self.objects.get(entry.oid).unwrap())
+                .filter(|obj| opts.gtype.as_ref().is_none_or(|gtype| obj.g_type.same_level(gtype)))
+                .filter(|obj| !opts.same_strand || obj.strand == query.strand)
+                .map(|obj| Neighbor { obj, distance: signed(obj) })
+                .filter(|neighbor| neighbor.distance.unsigned_abs() as usize <= radius && accept(neighbor.distance))
+                .collect();
+            if hits.len() >= k || radius >= max_radius {
+                hits.sort_by_key(|neighbor| (neighbor.distance.abs(), neighbor.distance, neighbor.obj.interval.start, neighbor.obj.id));
+                hits.truncate(k);
+                return hits;
+            }
+            radius = max_radius.min(radius.saturating_mul(2));
+        }
+    }
+
+    // the query object with all of its ancestors and descendants if it is part of the group
+    fn relatives(&self, query: &GffObject) -> HashSet<usize> {
+        let mut relatives = HashSet::new();
+        let qid = match query.id {
+            Some(qid) if self.objects.get(qid) == Some(query) => qid,
+            _ => return relatives,
+        };
+        relatives.insert(qid);
+        for links in [|obj: &GffObject| obj.parents.clone(), |obj: &GffObject| obj.children.clone()] {
+            let mut stack = links(query);
+            while let Some(oid) = stack.pop() {
+                if relatives.insert(oid) {
+                    stack.extend(links(self.objects.get(oid).unwrap()));
+                }
+            }
+        }
+        relatives
+    }
+
+    // false whenever objects were added or could have been modified since the last query
+    pub fn is_indexed(&self) -> bool {
+        self.seq_index.get().is_some()
+    }
+
+    // objects overlapping seqid:start-end (1-based, inclusive) sorted by position
+    // optionally restricted to one strand and level of feature types (Types::Gene also matches ncRNA_genes)
+    // the index is built on the first query after objects were added or modified
+    pub fn find_overlaps(&self, seqid: &str, start: usize, end: usize, strand: Option<char>, gtype: Option<Types>) -> Vec<&GffObject> {
+        let tree = match self.seq_index().trees.get(seqid) {
+            Some(tree) => tree,
+            None => return Vec::new(),
+        };
+        let mut hits: Vec<&GffObject> = tree
+            .find(start..end + 1)
+            .into_iter()
+            .map(|entry| self.objects.get(entry.oid).unwrap())
+            .filter(|obj| strand.is_none_or(|strand| obj.strand == strand))
+            .filter(|obj| gtype.as_ref().is_none_or(|gtype| obj.g_type.same_level(gtype)))
+            .collect();
+        hits.sort_by_key(|obj| (obj.interval.start, obj.interval.end, obj.id));
+        hits
+    }
+
+    pub fn create_parent(&mut self, oid: usize) -> Result<usize, Error> {
         // create a parent object for the given object
         // return the ID of the parent object
         // if the parent object already exists, return its ID
         // if the parent object does not exist, create it and return its ID
         // if the parent object can not be created, return an error
+        // objects with several parents (GFF3 Parent=tx1,tx2) get the first one - see synthesize_parents for all of them
+        let parent_id_str = self.objects.get(oid)
+            .ok_or(Error::InvalidObject { oid, reason: "object not found".to_string() })?
+            .parent_id_strs.first().cloned()
+            .ok_or(Error::InvalidObject { oid, reason: "object has no parent ID".to_string() })?;
+        self.create_parent_with_id(oid, parent_id_str)
+    }
 
-        // make sure the type is compatible with the type of parent ID extracted
-        // for example, exon should have transcript_id (but should also check for the available gene_id as well to be propagated upwards)
-        // when creating the parent object - can provide it with the current attribtues, and let it extract form them what is needed
-        let mut parent_id: Option<usize> = None;
-        match obj.g_type {
-            Types::Transcript => {
-                // create gene object
-            }
-            Types::Exon | Types::CDS => {
-                // create transcript object
-            },
-            _ => {
-                // create parent object based on the type of the object
+    fn create_parent_with_id(&mut self, oid: usize, parent_id_str: String) -> Result<usize, Error> {
+        // the type of the parent follows from the type of the object (exon -> transcript, transcript -> gene)
+        // and the parent takes over the shared attributes of the object, so that for example
+        // the gene_id of an exon ends up on the synthesized transcript and can be used to create the gene in turn
+        if let Some(parent_oid) = self.resolve_parent(oid, &parent_id_str) {
+            return Ok(parent_oid);
+        }
+        let obj = self.objects.get(oid).ok_or(Error::InvalidObject { oid, reason: "object not found".to_string() })?;
+        let parent_type = match obj.g_type.parent_type() {
+            Some(parent_type) => parent_type,
+            None => return Err(Error::MissingParent { loc: Location::default(), parent_id: parent_id_str }),
+        };
+
+        let parent = GffObject::new_parent(obj, parent_type, parent_id_str);
+        Ok(self.add_object(parent))
+    }
+
+    fn synthesize_parents(&mut self) -> Result<Vec<usize>, Error> {
+        // create all parents which are referenced but not present in the group
+        // newly created objects are appended to the tree and visited by the same loop
+        // so that missing genes are created from the synthesized transcripts
+        let mut synthesized: Vec<usize> = Vec::new();
+        let mut oid = 0;
+        while oid < self.objects.len() {
+            let obj = self.objects.get(oid).unwrap();
+            if obj.g_type.parent_type().is_some() {
+                let missing: Vec<String> = obj.parent_id_strs.iter()
+                    .filter(|parent_id_str| self.resolve_parent(oid, parent_id_str).is_none())
+                    .cloned()
+                    .collect();
+                for parent_id_str in missing {
+                    synthesized.push(self.create_parent_with_id(oid, parent_id_str)?);
+                }
             }
+            oid += 1;
         }
-        match parent_id {
-            Some(pid) => Ok(pid),
-            None => Err("Parent object could not be created")?,
+        Ok(synthesized)
+    }
+
+    fn share_attributes(&mut self, oid: usize) {
+        // restrict the attributes of a synthesized object to the ones all of its children agree on
+        let obj = self.objects.get(oid).unwrap();
+        let is_gff = obj.is_gff();
+        let mut attrs = obj.attrs.clone();
+        for child_id in obj.children() {
+            let child = self.objects.get(*child_id).unwrap();
+            attrs.retain(|k, v| (is_gff && k == "ID") || child.attrs.contains(k, v));
         }
+        self.objects.get_mut(oid).unwrap().attrs = attrs;
     }
 
-    pub fn reset_intervals(&mut self) {
-        // objects themselves have no colntrol over the intervals of their children, since children are stored as indices only
-        // after children have been added to an object
-        // we need to make sure parents have intervals that cover all of their children end-to-end
-        
-        // for each object, get the min(start), max(end) of its children
-        // set the interval of the object to cover all of its children
-        // propagate further recursively, so that the parents of the object also have intervals that cover all of their children
+    fn reset_subtree(&mut self, oid: usize) -> (usize, usize) {
+        // set the interval of the object to min(start), max(end) of its children (after resetting the children themselves)
+        // and return the resulting span
+        let children = self.objects.get(oid).unwrap().children.clone();
+        if children.is_empty() {
+            let interval = &self.objects.get(oid).unwrap().interval;
+            return (interval.start, interval.end);
+        }
+        let mut start = usize::MAX;
+        let mut end = 0;
+        for child_id in children {
+            let (child_start, child_end) = self.reset_subtree(child_id);
+            start = start.min(child_start);
+            end = end.max(child_end);
+        }
+        self.objects.get_mut(oid).unwrap().interval = Interval::new(start..end).unwrap();
+        (start, end)
+    }
+
+    fn span_of_children(&self, oid: usize) -> Option<(usize, usize)> {
+        let children = self.objects.get(oid)?.children();
+        let start = children.iter().map(|c| self.objects.get(*c).unwrap().interval.start).min()?;
+        let end = children.iter().map(|c| self.objects.get(*c).unwrap().interval.end).max()?;
+        Some((start, end))
+    }
+
+    fn resolve_parent(&self, oid: usize, parent_id_str: &str) -> Option<usize> {
+        // find the object the parent ID refers to
+        // the same ID can be shared by objects of different types (gene_id "g1"; transcript_id "g1";)
+        // so prefer the candidate of the type expected for a parent of the object
+        // IDs which collided between inputs (CollisionPolicy::Namespace) only resolve within the input of the object
+        let candidates = self.id_map.get(parent_id_str)?;
+        let obj = self.objects.get(oid)?;
+        let expected = obj.g_type.parent_type();
+        let input = obj.origin.as_ref().map(|origin| origin.input);
+        let namespaced = self.namespaced.contains(parent_id_str);
+        // objects at another level of the hierarchy (transcript "g1" for the gene_id "g1" of a transcript) are never parents
+        let mut candidates = candidates
+            .iter()
+            .copied()
+            .filter(|pid| *pid != oid)
+            .filter(|pid| !namespaced || self.objects.get(*pid).unwrap().origin.as_ref().map(|origin| origin.input) == input)
+            .filter(|pid| {
+                let gtype = &self.objects.get(*pid).unwrap().g_type;
+                expected.as_ref().is_none_or(|expected| gtype.same_level(expected))
+                    || !(gtype.is_gene_like() || gtype.is_transcript_like())
+            })
+            .peekable();
+        let first = *candidates.peek()?;
+        candidates
+            .find(|pid| expected.as_ref().is_some_and(|expected| self.objects.get(*pid).unwrap().g_type.same_level(expected)))
+            .or(Some(first))
     }
 
-    fn finalize(&mut self) -> Result<(), Box<dyn Error>> {
+    pub fn finalize(&mut self) -> Result<Vec<Orphan>, Error> {
         // finalize the internals of the tree
         // use the attributes of the objects, to figure out the parent/child relationships and set children/parent fields accordingly
-        
+        // all objects are already registered in the id_map, so the order in which they were added does not matter
+        // parents referenced by ID but missing from the input (exon-only GTF) are synthesized first
+        // returns the objects whose parent could not be found or created
+
+        let synthesized = self.synthesize_parents()?;
+
         // we can not borrow and modify the objects at the same time
-        // so we will create a new vector of objects with the correct parent/child relationships
-        let mut hierarchy_updates: Vec<(usize, GffObject)> = Vec::new(); // TODO: ideally we do not create copies here, since some internals are heavy (attributes, for example)
-        // collect parent-child relationships
-        for obj in &self.objects {
+        // so collect (parent, child) index pairs first and apply them afterwards
+        let mut hierarchy_updates: Vec<(usize, usize)> = Vec::new();
+        let mut orphans: Vec<Orphan> = Vec::new();
+        for oid in 0..self.objects.len() {
+            let obj = self.objects.get(oid).unwrap();
             // make sure each object already has an ID assigned (should be handled when creating transcriptome)
-            if (obj.id.is_none()){
-                Err("Object does not have an ID assigned")?;
+            if obj.id != Some(oid) {
+                return Err(Error::InvalidObject { oid, reason: "object does not have an ID assigned".to_string() });
             }
-            if let Some(parent_id_str) = obj.parent_id_str.clone() {
-                // check if parent already exists
-                if let Some(parent_oid) = self.id_map.get(&parent_id_str) {
-                    hierarchy_updates.push((*parent_oid, obj.clone()));
-                }
-                {
-                    // TODO: parent not found but an ID for it exists
-                    // need to create parent object
-                    // recursively create parent objects based on the type of the current object, reconstructing the necessary components
-                    // unimplemented!()
+            // GFF3 records can list several parents - the object becomes a child of each of them
+            for parent_id_str in &obj.parent_id_strs {
+                match self.resolve_parent(oid, parent_id_str) {
+                    Some(parent_oid) => hierarchy_updates.push((parent_oid, oid)),
+                    None => orphans.push(Orphan { oid, parent_id: parent_id_str.clone() }),
                 }
             }
         }
 
+        // reset any links from previous calls so that finalize can be repeated after adding objects
+        for oid in 0..self.objects.len() {
+            let obj = self.objects.get_mut(oid).unwrap();
+            obj.children.clear();
+            obj.parents.clear();
+        }
+
         // Assigning parent/child relationships to the objects
-        for (parent_id, child_obj) in hierarchy_updates {
-            if let Some(parent_obj) = self.objects.get_mut(parent_id) {
-                parent_obj.add_child(&child_obj);
-            } else {
-                Err("Parent object not found")?;
+        for (parent_id, child_id) in hierarchy_updates {
+            match self.objects.get_mut(parent_id) {
+                Some(parent_obj) => parent_obj.children.push(child_id),
+                None => return Err(Error::InvalidObject { oid: parent_id, reason: "parent object not found".to_string() }),
             }
+            self.objects.get_mut(child_id).unwrap().parents.push(parent_id);
         }
 
-        Ok(())
+        // synthesized objects were created from a single child - extend them to all of their children
+        for oid in synthesized {
+            self.share_attributes(oid);
+        }
+        self.reset_intervals();
+
+        Ok(orphans)
     }
 
+    // mutable view of a transcript - None if the object does not exist or is not transcript-like
     pub fn get_transcript<'a>(&'a mut self, tid: usize) -> Option<TranscriptRef<'a, Transcriptome>> {
+        if !self.objects.get(tid)?.g_type.is_transcript_like() {
+            return None;
+        }
         Some(TranscriptRef::new(self, tid))
     }
+
+    // all genes in the order they were added
+    // views are based on the hierarchy built by finalize
+    pub fn genes(&self) -> impl Iterator<Item = GeneView<'_, Transcriptome>> {
+        self.oids_where(Types::is_gene_like).map(move |gid| GeneView::new(self, gid))
+    }
+
+    // genes on the given sequence, optionally restricted to one strand
+    pub fn genes_on<'a>(&'a self, seqid: &'a str, strand: Option<char>) -> impl Iterator<Item = GeneView<'a, Transcriptome>> {
+        self.genes().filter(move |gene| is_on(gene.object(), seqid, strand))
+    }
+
+    pub fn transcripts(&self) -> impl Iterator<Item = TranscriptView<'_, Transcriptome>> {
+        self.oids_where(Types::is_transcript_like).map(move |tid| TranscriptView::new(self, tid))
+    }
+
+    pub fn transcripts_on<'a>(&'a self, seqid: &'a str, strand: Option<char>) -> impl Iterator<Item = TranscriptView<'a, Transcriptome>> {
+        self.transcripts().filter(move |transcript| is_on(transcript.object(), seqid, strand))
+    }
+
+    // exons of a transcript in transcription order - empty if the object is not a transcript
+    pub fn exons_of(&self, tid: usize) -> impl Iterator<Item = &GffObject> {
+        self.transcript(tid).map(|transcript| transcript.exons()).unwrap_or_default().into_iter()
+    }
+
+    fn oids_where(&self, predicate: fn(&Types) -> bool) -> impl Iterator<Item = usize> + '_ {
+        (0..self.objects.len()).filter(move |oid| predicate(&self.objects.get(*oid).unwrap().g_type))
+    }
+
+    // read-only view of a gene - None if the object is not gene-like
+    pub fn gene(&self, gid: usize) -> Option<GeneView<'_, Transcriptome>> {
+        match self.objects.get(gid)?.g_type.is_gene_like() {
+            true => Some(GeneView::new(self, gid)),
+            false => None,
+        }
+    }
+
+    // read-only view of a transcript - None if the object is not transcript-like
+    pub fn transcript(&self, tid: usize) -> Option<TranscriptView<'_, Transcriptome>> {
+        match self.objects.get(tid)?.g_type.is_transcript_like() {
+            true => Some(TranscriptView::new(self, tid)),
+            false => None,
+        }
+    }
+}
+
+fn is_on(obj: &GffObject, seqid: &str, strand: Option<char>) -> bool {
+    obj.seqid == seqid && strand.is_none_or(|strand| obj.strand == strand)
+}
+
+// name of an input without directories and extensions (data/sample2.gtf.gz -> sample2)
+fn file_stem(fname: &str) -> &str {
+    let name = fname.rsplit('/').next().unwrap_or(fname);
+    name.split('.').next().filter(|stem| !stem.is_empty()).unwrap_or(name)
+}
+
+// prefix the given IDs wherever the object uses them - as its own ID, parent IDs and in the attributes holding them
+fn rename_ids(obj: &mut GffObject, ids: &HashSet<String>, prefix: &str) {
+    let rename = |id: &mut String| {
+        if ids.contains(id.as_str()) {
+            *id = format!("{}{}", prefix, id);
+        }
+    };
+    if let Some(id_str) = obj.id_str.as_mut() {
+        rename(id_str);
+    }
+    obj.parent_id_strs.iter_mut().for_each(rename);
+    let keys: &[&str] = if obj.is_gff() { &["ID", "Parent"] } else { &["gene_id", "transcript_id"] };
+    for key in keys {
+        obj.attrs.values_mut(key).for_each(rename);
+    }
 }
 
 #[cfg(test)]
@@ -219,8 +817,402 @@ mod tests {
         let mut transcriptome = Transcriptome::from_file(fname).unwrap();
         let res = transcriptome.finalize();
         assert!(res.is_ok());
-        assert_eq!(transcriptome.objects().len(), 2);
+        // gene is synthesized from the gene_id of the transcript
+        assert_eq!(transcriptome.objects().len(), 3);
+        let transcript = transcriptome.get(0).unwrap();
+        assert_eq!(transcript.children(), &[1]);
+        assert_eq!(transcript.parent(), Some(2));
+        assert_eq!(transcriptome.get(1).unwrap().parent(), Some(0));
 
         std::fs::remove_file(fname).unwrap();
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_from_reader() {
+        let lines = "chr1\ttest\ttranscript\t1\t30\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t20\t30\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        assert_eq!(transcriptome.objects().len(), 2);
+        assert_eq!(transcriptome.get(0).unwrap().get_type(), Types::Transcript);
+        transcriptome.add_from_reader(lines.as_bytes()).unwrap();
+        assert_eq!(transcriptome.objects().len(), 4);
+    }
+
+    #[test]
+    fn test_format_detection() {
+        // GFF3 without the gff-version pragma - the hierarchy is built from ID/Parent
+        let lines = "chr1\ttest\tgene\t1\t30\t.\t+\t.\tID=g1\n\
+                     chr1\ttest\tmRNA\t1\t30\t.\t+\t.\tID=t1;Parent=g1\n\
+                     chr1\ttest\texon\t20\t30\t.\t+\t.\tParent=t1\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+        assert!(transcriptome.objects().iter().all(|obj| obj.is_gff()));
+        assert_eq!(transcriptome.get(2).unwrap().parents, vec![1]);
+
+        // GTF with a value containing '=' is still GTF
+        let lines = "chr1\ttest\ttranscript\t1\t30\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\"; note \"a=b\";\n\
+                     chr1\ttest\texon\t20\t30\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        let transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        assert!(transcriptome.objects().iter().all(|obj| !obj.is_gff()));
+        assert_eq!(transcriptome.get(0).unwrap().attrs.get("note").unwrap(), "a=b");
+
+        // an explicit format overrides detection
+        let mut reader = TReader::default();
+        reader.set_format(Some(Format::GFF));
+        reader.add_reader("test.gff", "chr1\ttest\tgene\t1\t30\t.\t+\t.\tID=g1\n".as_bytes()).unwrap();
+        let mut transcriptome = Transcriptome::new();
+        transcriptome.add_from_treader(&mut reader).unwrap();
+        assert_eq!(transcriptome.get_by_id("g1").unwrap().get_type(), Types::Gene);
+    }
+
+    #[test]
+    fn test_comments() {
+        let lines = "##gff-version 3\n\
+                     ##sequence-region chr1 1 1000\n\
+                     chr1\ttest\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                     ###\n\
+                     chr1\ttest\tgene\t200\t300\t.\t+\t.\tID=g2\n\
+                     # end\n";
+        let transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        assert_eq!(transcriptome.comments().len(), 4);
+        assert_eq!(transcriptome.comments_before(0).map(|c| c.text.as_str()).collect::<Vec<_>>(),
+                   vec!["##gff-version 3", "##sequence-region chr1 1 1000"]);
+        assert_eq!(transcriptome.comments_before(1).map(|c| c.text.as_str()).collect::<Vec<_>>(), vec!["###"]);
+        assert_eq!(transcriptome.comments_before(2).map(|c| c.text.as_str()).collect::<Vec<_>>(), vec!["# end"]);
+        assert_eq!(transcriptome.directives("sequence-region"), vec!["chr1 1 1000"]);
+    }
+
+    #[test]
+    fn test_origin() {
+        let first = "# sample 1\n\
+                     chr1\ttest\texon\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        let second = "chr1\ttest\ttranscript\t1\t100\t.\t+\t.\tgene_id \"g2\"; transcript_id \"t2\";\n\
+                      chr1\ttest\texon\t1\t100\t.\t+\t.\tgene_id \"g2\"; transcript_id \"t2\";\n";
+        let mut transcriptome = Transcriptome::new();
+        let mut reader = TReader::default();
+        reader.add_reader("sample1.gtf", first.as_bytes()).unwrap();
+        transcriptome.add_from_treader(&mut reader).unwrap();
+        let mut reader = TReader::default();
+        reader.add_reader("sample2.gtf", second.as_bytes()).unwrap();
+        transcriptome.add_from_treader(&mut reader).unwrap();
+        transcriptome.finalize().unwrap();
+
+        assert_eq!(transcriptome.inputs(), &["sample1.gtf".to_string(), "sample2.gtf".to_string()]);
+        let origin = transcriptome.get(2).unwrap().origin().unwrap();
+        assert_eq!((origin.input, &*origin.fname, origin.line_num), (1, "sample2.gtf", 2));
+        // synthesized parents keep the input of the record they were created from
+        assert_eq!(transcriptome.objects_from(0).count(), 3);
+        assert!(transcriptome.objects_from(0).skip(1).all(|obj| obj.origin().unwrap().line_num == 0));
+        assert_eq!(transcriptome.objects_from(1).count(), 3);
+
+        transcriptome.label_origin("source_file");
+        assert!(transcriptome.get(0).unwrap().gtf().ends_with("source_file \"sample1.gtf\";"));
+    }
+
+    #[test]
+    fn test_id_collisions() {
+        let sample = "chr1\tStringTie\ttranscript\t1\t100\t.\t+\t.\tgene_id \"STRG.1\"; transcript_id \"STRG.1.1\";\n\
+                      chr1\tStringTie\texon\t1\t100\t.\t+\t.\tgene_id \"STRG.1\"; transcript_id \"STRG.1.1\";\n";
+        let load = |policy: CollisionPolicy| {
+            let mut transcriptome = Transcriptome::new();
+            transcriptome.set_collision_policy(policy);
+            let mut reader = TReader::default();
+            reader.add_reader("data/sample1.gtf", sample.as_bytes()).unwrap();
+            reader.add_reader("data/sample2.gtf.gz", sample.as_bytes()).unwrap();
+            transcriptome.add_from_treader(&mut reader).map(|_| transcriptome)
+        };
+
+        match load(CollisionPolicy::Error) {
+            Err(Error::DuplicateId { loc, id }) => {
+                assert_eq!(id, "STRG.1.1");
+                assert_eq!((loc.fname.as_str(), loc.line_num), ("data/sample2.gtf.gz", 1));
+            }
+            res => panic!("unexpected result: {:?}", res.map(|_| ())),
+        }
+
+        let mut transcriptome = load(CollisionPolicy::Rename).unwrap();
+        assert_eq!(transcriptome.oids_by_id("STRG.1.1", None), vec![0]);
+        assert_eq!(transcriptome.oids_by_id("sample2_STRG.1.1", None), vec![2]);
+        assert!(transcriptome.get(3).unwrap().gtf().ends_with("gene_id \"sample2_STRG.1\"; transcript_id \"sample2_STRG.1.1\";"));
+        assert!(transcriptome.finalize().unwrap().is_empty());
+        assert_eq!(transcriptome.get(3).unwrap().parents(), &[2]);
+        // each sample gets its own gene
+        assert_eq!(transcriptome.objects().len(), 6);
+
+        let mut transcriptome = load(CollisionPolicy::Namespace).unwrap();
+        assert_eq!(transcriptome.oids_by_id("STRG.1.1", None), vec![0, 2]);
+        assert_eq!(transcriptome.oids_by_id("STRG.1.1", Some(1)), vec![2]);
+        assert!(transcriptome.finalize().unwrap().is_empty());
+        assert_eq!(transcriptome.get(1).unwrap().parents(), &[0]);
+        assert_eq!(transcriptome.get(3).unwrap().parents(), &[2]);
+        assert_eq!(transcriptome.objects().len(), 6);
+        assert_ne!(transcriptome.get(0).unwrap().parents(), transcriptome.get(2).unwrap().parents());
+
+        // exon-only inputs only reference their transcripts and genes - both are synthesized per input
+        let exons = "chr1\tStringTie\texon\t1\t100\t.\t+\t.\tgene_id \"STRG.1\"; transcript_id \"STRG.1.1\";\n";
+        let load_exons = |policy: CollisionPolicy, fnames: &[&str]| {
+            let mut transcriptome = Transcriptome::new();
+            transcriptome.set_collision_policy(policy);
+            let mut reader = TReader::default();
+            for fname in fnames {
+                reader.add_reader(fname, exons.as_bytes()).unwrap();
+            }
+            transcriptome.add_from_treader(&mut reader).unwrap();
+            assert!(transcriptome.finalize().unwrap().is_empty());
+            transcriptome
+        };
+        let gene_ids = |transcriptome: &Transcriptome| {
+            transcriptome.genes().map(|g| g.object().get_attr("gene_id").unwrap().clone()).collect::<Vec<_>>()
+        };
+
+        let transcriptome = load_exons(CollisionPolicy::Rename, &["data/sample1.gtf", "data/sample2.gtf"]);
+        assert_eq!(transcriptome.objects().len(), 6);
+        assert_eq!(gene_ids(&transcriptome), vec!["STRG.1", "sample2_STRG.1"]);
+        assert!(transcriptome.get(1).unwrap().gtf().ends_with("gene_id \"sample2_STRG.1\"; transcript_id \"sample2_STRG.1.1\";"));
+
+        let transcriptome = load_exons(CollisionPolicy::Namespace, &["data/sample1.gtf", "data/sample2.gtf"]);
+        assert_eq!(transcriptome.objects().len(), 6);
+        assert_eq!(gene_ids(&transcriptome), vec!["STRG.1", "STRG.1"]);
+        assert_eq!(transcriptome.oids_by_id("STRG.1", Some(0)).len(), 1);
+        assert_eq!(transcriptome.oids_by_id("STRG.1", Some(1)).len(), 1);
+
+        // inputs with the same file name get distinct prefixes
+        let transcriptome = load_exons(CollisionPolicy::Rename, &["a/sample.gtf", "b/sample.gtf", "c/sample.gtf"]);
+        assert_eq!(transcriptome.objects().len(), 9);
+        assert_eq!(gene_ids(&transcriptome), vec!["STRG.1", "sample_STRG.1", "sample2_STRG.1"]);
+
+        // references to IDs defined by an earlier input link the inputs
+        let genes = "chr1\ttest\tgene\t1\t100\t.\t+\t.\tID=g1\n";
+        let transcripts = "chr1\ttest\tmRNA\t1\t100\t.\t+\t.\tID=t1;Parent=g1\n";
+        let mut transcriptome = Transcriptome::new();
+        transcriptome.set_collision_policy(CollisionPolicy::Error);
+        let mut reader = TReader::default();
+        reader.add_reader("genes.gff", genes.as_bytes()).unwrap();
+        reader.add_reader("transcripts.gff", transcripts.as_bytes()).unwrap();
+        transcriptome.add_from_treader(&mut reader).unwrap();
+        transcriptome.finalize().unwrap();
+        assert_eq!(transcriptome.get(1).unwrap().parents(), &[0]);
+    }
+
+    #[test]
+    fn test_iterators() {
+        let lines = "chr1\ttest\ttranscript\t100\t600\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t501\t600\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\ttranscript\t1000\t1200\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t2\";\n\
+                     chr1\ttest\texon\t1000\t1200\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t2\";\n\
+                     chr2\ttest\ttranscript\t1\t50\t.\t+\t.\tgene_id \"g3\"; transcript_id \"t3\";\n\
+                     chr2\ttest\texon\t1\t50\t.\t+\t.\tgene_id \"g3\"; transcript_id \"t3\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+
+        assert_eq!(transcriptome.genes().count(), 3);
+        assert_eq!(transcriptome.transcripts().map(|t| t.tid()).collect::<Vec<_>>(), vec![0, 3, 5]);
+        assert_eq!(transcriptome.transcripts_on("chr1", None).count(), 2);
+        let genes: Vec<_> = transcriptome.genes_on("chr1", Some('-')).collect();
+        assert_eq!(genes.len(), 1);
+        assert_eq!(genes[0].object().get_attr("gene_id").unwrap(), "g2");
+        assert_eq!(transcriptome.exons_of(0).map(|e| e.interval().start).collect::<Vec<_>>(), vec![100, 501]);
+        assert_eq!(transcriptome.exons_of(1).count(), 0);
+    }
+
+    #[test]
+    fn test_lookup() {
+        let lines = "chr1\ttest\tgene\t100\t600\t.\t+\t.\tgene_id \"ENSG1\"; gene_name \"HMGN1\";\n\
+                     chr1\ttest\ttranscript\t100\t600\t.\t+\t.\tgene_id \"ENSG1\"; transcript_id \"ENST1\"; gene_name \"HMGN1\";\n\
+                     chr1\ttest\texon\t100\t600\t.\t+\t.\tgene_id \"ENSG1\"; transcript_id \"ENST1\"; gene_name \"HMGN1\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+
+        assert_eq!(transcriptome.get_by_id("ENST1").unwrap().get_type(), Types::Transcript);
+        assert_eq!(transcriptome.get_by_id("ENSG1").unwrap().get_type(), Types::Gene);
+        assert!(transcriptome.get_by_id("ENST2").is_none());
+        assert_eq!(transcriptome.get_by_attr("gene_name", "HMGN1").len(), 3);
+        assert!(transcriptome.get_by_attr("gene_name", "HMGN2").is_empty());
+        assert!(transcriptome.get(3).is_none());
+        assert!(transcriptome.get_transcript(1).is_some());
+        assert!(transcriptome.get_transcript(0).is_none());
+        assert!(transcriptome.get_transcript(3).is_none());
+    }
+
+    #[test]
+    fn test_find_overlaps() {
+        let lines = "chr21\ttest\ttranscript\t39341936\t39349011\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr21\ttest\texon\t39341936\t39342000\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr21\ttest\texon\t39349000\t39349011\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr21\ttest\ttranscript\t39340000\t39341936\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t2\";\n\
+                     chr1\ttest\ttranscript\t39341936\t39349011\t.\t+\t.\tgene_id \"g3\"; transcript_id \"t3\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        assert!(!transcriptome.is_indexed());
+
+        let ids = |hits: Vec<&GffObject>| hits.iter().map(|o| o.id.unwrap()).collect::<Vec<usize>>();
+        assert_eq!(ids(transcriptome.find_overlaps("chr21", 39341936, 39349011, None, None)), vec![3, 1, 0, 2]);
+        assert!(transcriptome.is_indexed());
+        assert_eq!(ids(transcriptome.find_overlaps("chr21", 39341936, 39349011, Some('+'), None)), vec![1, 0, 2]);
+        assert_eq!(ids(transcriptome.find_overlaps("chr21", 39341936, 39349011, None, Some(Types::Transcript))), vec![3, 0]);
+        // coordinates are inclusive
+        assert_eq!(ids(transcriptome.find_overlaps("chr21", 39349011, 39349011, None, None)), vec![0, 2]);
+        assert_eq!(ids(transcriptome.find_overlaps("chr21", 39349012, 39350000, None, None)), Vec::<usize>::new());
+        assert!(transcriptome.find_overlaps("chrX", 1, 1000, None, None).is_empty());
+
+        // objects added later are found after reindexing
+        transcriptome.add_object(GffObject::new("chrX\ttest\tgene\t100\t200\t.\t+\t.\tgene_id \"g4\";", false).unwrap());
+        assert!(!transcriptome.is_indexed());
+        assert_eq!(ids(transcriptome.find_overlaps("chrX", 1, 1000, None, None)), vec![5]);
+
+        // type filters match the level of the hierarchy - GFF3 mRNAs are transcripts
+        transcriptome.add_object(GffObject::new("chrX\ttest\tmRNA\t150\t300\t.\t+\t.\tID=m1", true).unwrap());
+        assert_eq!(ids(transcriptome.find_overlaps("chrX", 1, 1000, None, Some(Types::Transcript))), vec![6]);
+        assert_eq!(ids(transcriptome.find_overlaps("chrX", 1, 1000, None, Some(Types::Gene))), vec![5]);
+        assert!(transcriptome.find_overlaps("chrX", 1, 1000, None, Some(Types::Exon)).is_empty());
+    }
+
+    #[test]
+    fn test_nearest() {
+        let lines = "chr1\ttest\tgene\t1000\t2500\t.\t+\t.\tgene_id \"g1\";\n\
+                     chr1\ttest\tgene\t5000\t6000\t.\t-\t.\tgene_id \"g2\";\n\
+                     chr1\ttest\tgene\t20000\t30000\t.\t+\t.\tgene_id \"g3\";\n\
+                     chr1\tnovel\ttranscript\t3000\t4000\t.\t-\t.\tgene_id \"n1\"; transcript_id \"n1.1\";\n\
+                     chr1\tnovel\texon\t3000\t4000\t.\t-\t.\tgene_id \"n1\"; transcript_id \"n1.1\";\n\
+                     chr2\ttest\tgene\t3000\t4000\t.\t+\t.\tgene_id \"g4\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+        let query = transcriptome.get_by_id("n1.1").unwrap();
+        let genes = NearestOptions { gtype: Some(Types::Gene), ..Default::default() };
+        let gene_id = |neighbor: &Neighbor| neighbor.obj.get_attr("gene_id").unwrap().clone();
+
+        // the gene of the novel transcript itself is not reported
+        let nearest = transcriptome.nearest(query, &genes).unwrap();
+        assert_eq!((gene_id(&nearest), nearest.distance), ("g1".to_string(), 500));
+
+        // the query is on the - strand - features at lower coordinates are downstream
+        let hits: Vec<(String, i64)> = transcriptome.k_nearest(query, 5, &genes).iter().map(|n| (gene_id(n), n.distance)).collect();
+        assert_eq!(hits, vec![("g1".to_string(), 500), ("g2".to_string(), -1000), ("g3".to_string(), -16000)]);
+
+        let close = NearestOptions { max_distance: Some(5000), ..genes.clone() };
+        assert_eq!(transcriptome.k_nearest(query, 5, &close).len(), 2);
+        let stranded = NearestOptions { same_strand: true, ..genes.clone() };
+        assert_eq!(gene_id(&transcriptome.nearest(query, &stranded).unwrap()), "g2");
+
+        let (upstream, downstream) = transcriptome.flanking(query, &genes);
+        assert_eq!(gene_id(&upstream.unwrap()), "g2");
+        assert_eq!(gene_id(&downstream.unwrap()), "g1");
+
+        // queries need not be part of the transcriptome
+        let query = GffObject::new("chr1\ttest\texon\t1500\t1600\t.\t.\t.\tgene_id \"x\";", false).unwrap();
+        let nearest = transcriptome.nearest(&query, &genes).unwrap();
+        assert_eq!((gene_id(&nearest), nearest.distance), ("g1".to_string(), 0));
+
+        // type filters match the level of the hierarchy - GFF3 mRNAs are transcripts, ncRNA_genes are genes
+        let lines = "chr1\ttest\tgene\t1000\t2500\t.\t+\t.\tID=g1\n\
+                     chr1\ttest\tmRNA\t1000\t2500\t.\t+\t.\tID=m1;Parent=g1\n\
+                     chr1\ttest\tncRNA_gene\t5000\t6000\t.\t-\t.\tID=g2\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+        let query = GffObject::new("chr1\ttest\texon\t4500\t4600\t.\t.\t.\tID=x", true).unwrap();
+        let id = |neighbor: Option<Neighbor>| neighbor.unwrap().obj.id_str.clone().unwrap();
+        assert_eq!(id(transcriptome.nearest(&query, &genes)), "g2");
+        let transcripts = NearestOptions { gtype: Some(Types::Transcript), ..Default::default() };
+        assert_eq!(id(transcriptome.nearest(&query, &transcripts)), "m1");
+    }
+
+    #[test]
+    fn test_finalize_any_order() {
+        let mut transcriptome = Transcriptome::new();
+        let e1 = transcriptome.add_object(GffObject::new("chr1\ttest\texon\t1\t10\t.\t+\t.\tID=e1;Parent=t1", true).unwrap());
+        let e2 = transcriptome.add_object(GffObject::new("chr1\ttest\texon\t20\t30\t.\t+\t.\tID=e2;Parent=t1", true).unwrap());
+        let tid = transcriptome.add_object(GffObject::new("chr1\ttest\tmRNA\t1\t30\t.\t+\t.\tID=t1;Parent=g1", true).unwrap());
+        let gid = transcriptome.add_object(GffObject::new("chr1\ttest\tgene\t1\t30\t.\t+\t.\tID=g1", true).unwrap());
+        let orphan = transcriptome.add_object(GffObject::new("chr1\ttest\tmisc_feature\t40\t50\t.\t+\t.\tID=m1;Parent=t2", true).unwrap());
+
+        let orphans = transcriptome.finalize().unwrap();
+        assert_eq!(orphans, vec![Orphan { oid: orphan, parent_id: "t2".to_string() }]);
+        assert_eq!(transcriptome.get(tid).unwrap().children(), &[e1, e2]);
+        assert_eq!(transcriptome.get(tid).unwrap().parent(), Some(gid));
+        assert_eq!(transcriptome.get(gid).unwrap().children(), &[tid]);
+        assert_eq!(transcriptome.get(e1).unwrap().parent(), Some(tid));
+        assert_eq!(transcriptome.get(orphan).unwrap().parent(), None);
+
+        // repeated calls do not duplicate links
+        transcriptome.finalize().unwrap();
+        assert_eq!(transcriptome.get(tid).unwrap().children(), &[e1, e2]);
+    }
+
+    #[test]
+    fn test_create_parent() {
+        let mut transcriptome = Transcriptome::new();
+        let e1 = transcriptome.add_object(GffObject::new("chr1\ttest\texon\t20\t30\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\"; exon_number \"2\"; gene_name \"G1\"; tag \"a\";", false).unwrap());
+        let e2 = transcriptome.add_object(GffObject::new("chr1\ttest\texon\t1\t10\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\"; exon_number \"1\"; gene_name \"G1\"; tag \"b\";", false).unwrap());
+        let e3 = transcriptome.add_object(GffObject::new("chr1\ttest\texon\t40\t50\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t2\"; gene_name \"G1\";", false).unwrap());
+
+        let orphans = transcriptome.finalize().unwrap();
+        assert!(orphans.is_empty());
+        assert_eq!(transcriptome.objects().len(), 6);
+
+        let t1 = transcriptome.get(e1).unwrap().parent().unwrap();
+        assert_eq!(transcriptome.get(e2).unwrap().parent(), Some(t1));
+        let t1_obj = transcriptome.get(t1).unwrap();
+        assert_eq!(t1_obj.get_type(), Types::Transcript);
+        assert_eq!((t1_obj.interval.start, t1_obj.interval.end), (1, 30));
+        assert_eq!(t1_obj.get_attr("transcript_id").unwrap(), "t1");
+        assert_eq!(t1_obj.get_attr("gene_name").unwrap(), "G1");
+        assert!(t1_obj.get_attr("tag").is_none());
+
+        let t2 = transcriptome.get(e3).unwrap().parent().unwrap();
+        let gene = transcriptome.get(t1).unwrap().parent().unwrap();
+        assert_eq!(transcriptome.get(t2).unwrap().parent(), Some(gene));
+        let gene_obj = transcriptome.get(gene).unwrap();
+        assert_eq!(gene_obj.get_type(), Types::Gene);
+        assert_eq!(gene_obj.children(), &[t1, t2]);
+        assert_eq!((gene_obj.interval.start, gene_obj.interval.end), (1, 50));
+        assert_eq!(gene_obj.get_attrs().len(), 2);
+
+        // existing parents are returned as is
+        assert_eq!(transcriptome.create_parent(e1).unwrap(), t1);
+        assert!(transcriptome.create_parent(gene).is_err());
+    }
+
+    #[test]
+    fn test_reset_intervals() {
+        let mut transcriptome = Transcriptome::new();
+        // declared spans of the gene and transcript do not match the exons
+        let gid = transcriptome.add_object(GffObject::new("chr1\ttest\tgene\t1\t1000\t.\t+\t.\tID=g1", true).unwrap());
+        let tid = transcriptome.add_object(GffObject::new("chr1\ttest\tmRNA\t5\t25\t.\t+\t.\tID=t1;Parent=g1", true).unwrap());
+        transcriptome.add_object(GffObject::new("chr1\ttest\texon\t10\t20\t.\t+\t.\tID=e1;Parent=t1", true).unwrap());
+        transcriptome.add_object(GffObject::new("chr1\ttest\texon\t30\t40\t.\t+\t.\tID=e2;Parent=t1", true).unwrap());
+        let eid = transcriptome.add_object(GffObject::new("chr1\ttest\texon\t50\t60\t.\t+\t.\tID=e3", true).unwrap());
+        transcriptome.finalize().unwrap();
+
+        let interval = &transcriptome.get(tid).unwrap().interval;
+        assert_eq!((interval.start, interval.end), (10, 40));
+        let interval = &transcriptome.get(gid).unwrap().interval;
+        assert_eq!((interval.start, interval.end), (10, 40));
+
+        // adding an exon through the transcript view extends the transcript and the gene
+        let exon = transcriptome.get(eid).unwrap().clone();
+        transcriptome.get_transcript(tid).unwrap().add_child(&exon);
+        let interval = &transcriptome.get(tid).unwrap().interval;
+        assert_eq!((interval.start, interval.end), (10, 60));
+        let interval = &transcriptome.get(gid).unwrap().interval;
+        assert_eq!((interval.start, interval.end), (10, 60));
+        // the index is rebuilt lazily with the new spans
+        assert!(!transcriptome.is_indexed());
+        let hits: Vec<usize> = transcriptome.find_overlaps("chr1", 45, 45, None, None).iter().map(|o| o.id.unwrap()).collect();
+        assert_eq!(hits, vec![gid, tid]);
+    }
+
+    #[test]
+    fn test_multiple_parents() {
+        let mut transcriptome = Transcriptome::new();
+        let t1 = transcriptome.add_object(GffObject::new("chr1\ttest\tmRNA\t1\t30\t.\t+\t.\tID=t1", true).unwrap());
+        let t2 = transcriptome.add_object(GffObject::new("chr1\ttest\tmRNA\t1\t50\t.\t+\t.\tID=t2", true).unwrap());
+        let shared = transcriptome.add_object(GffObject::new("chr1\ttest\texon\t1\t10\t.\t+\t.\tParent=t1,t2", true).unwrap());
+        let e2 = transcriptome.add_object(GffObject::new("chr1\ttest\texon\t20\t30\t.\t+\t.\tParent=t1", true).unwrap());
+        let e3 = transcriptome.add_object(GffObject::new("chr1\ttest\texon\t40\t50\t.\t+\t.\tParent=t2", true).unwrap());
+
+        assert!(transcriptome.finalize().unwrap().is_empty());
+        assert_eq!(transcriptome.get(shared).unwrap().parents(), &[t1, t2]);
+        assert_eq!(transcriptome.get(t1).unwrap().children(), &[shared, e2]);
+        assert_eq!(transcriptome.get(t2).unwrap().children(), &[shared, e3]);
+        assert_eq!(transcriptome.get(shared).unwrap().gff(), "chr1\ttest\texon\t1\t10\t.\t+\t.\tParent=t1,t2");
+    }
+}
@@ -1,16 +1,24 @@
+pub mod attributes;
+pub mod error;
 pub mod utils;
 pub mod object;
 pub mod group;
 pub mod transcript;
+pub mod gene;
 pub mod treader;
+pub mod writer;
 pub mod learning;
 
 pub mod prelude {
-    pub use crate::object::GffObjectT;
-    pub use crate::group::{GffObjectGroupT, Transcriptome};
-    pub use crate::transcript::TranscriptRef;
+    pub use crate::attributes::Attributes;
+    pub use crate::error::Error;
+    pub use crate::object::{GffObjectT, Origin};
+    pub use crate::group::{CollisionPolicy, GffObjectGroupT, NearestOptions, Neighbor, Transcriptome};
+    pub use crate::transcript::{TranscriptRef, TranscriptView};
+    pub use crate::gene::GeneView;
     pub use crate::utils::*;
-    pub use crate::treader::TReader;
+    pub use crate::treader::{Comment, MergedTReader, ParsePolicy, ParseStats, TReader};
+    pub use crate::writer::{Gff3Writer, GtfWriter, Output};
 }
 
 pub use prelude::*;
@@ -1,16 +1,19 @@
 // defines traits and srtuct for the general purpose GffObject
 
 use std::convert::TryFrom;
-use std::error::Error;
 
 use bio::utils::Interval;
 use bio::data_structures::interval_tree::EntryT;
 
 use std::collections::HashMap;
 use std::cmp::Ordering;
+use std::sync::Arc;
 
+use crate::attributes::Attributes;
+use crate::error::{Error, Location};
 use crate::utils::*;
 
+#[allow(clippy::len_without_is_empty)] // len is the length of the feature, never empty
 pub trait GffObjectT: EntryT<N = usize> + std::fmt::Debug {
     fn seqid(&self) -> &str;
     fn strand(&self) -> char;
@@ -23,6 +26,10 @@ pub trait GffObjectT: EntryT<N = usize> + std::fmt::Debug {
     fn phase(&self) -> Option<u32> {
         None
     }
+    // input the object was read from - None for objects created in code
+    fn origin(&self) -> Option<&Origin> {
+        None
+    }
 
     fn get_attr(&self, key: &str) -> Option<&String> {
         self.get_attrs().get(key)
@@ -30,7 +37,7 @@ pub trait GffObjectT: EntryT<N = usize> + std::fmt::Debug {
 
     fn set_attr(&mut self, key: &str, value: String);
 
-    fn get_attrs(&self) -> &HashMap<String, String>;
+    fn get_attrs(&self) -> &Attributes;
 
     fn bed(&self) -> String;
     fn gtf(&self) -> String;
@@ -43,6 +50,8 @@ pub trait GffObjectT: EntryT<N = usize> + std::fmt::Debug {
 
     fn id(&self) -> Option<usize>; // returns unique idenfier of the object
     fn children(&self) -> &[usize];
+    fn parent(&self) -> Option<usize>; // numerical ID of the (first) parent object, set once the hierarchy is built
+    fn parents(&self) -> &[usize]; // all parents - GFF3 records can be shared between several transcripts
     fn add_child(&mut self, child: &dyn GffObjectT);
 
     fn set_type(&mut self, gtype: Types);
@@ -60,6 +69,14 @@ pub trait GffObjectT: EntryT<N = usize> + std::fmt::Debug {
     }
 }
 
+// input and line a record was read from
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Origin {
+    pub input: usize,    // number of the input in the order inputs were added to the reader or group
+    pub fname: Arc<str>, // name of the input ("-" for stdin, "<reader>" for unnamed readers)
+    pub line_num: usize, // 1-based line number - 0 for objects derived from a record (synthesized parents)
+}
+
 // implement a generic object type which can then be specialized into anything
 #[derive(Clone, Debug)]
 pub struct GffObject {
@@ -67,16 +84,20 @@ pub struct GffObject {
     pub strand: char,
     pub source: String,
     pub g_type: Types,
-    pub attrs: HashMap<String, String>,
+    pub score: Option<f32>, // None for "."
+    pub phase: Option<u32>, // None for "."
+    pub attrs: Attributes,
     extra_attrs: HashMap<String,String>, // extra attributes that are not part of the GFF/GTF 9th column
     
     pub id: Option<usize>, // numerical ID assigned by container or otherwise
     pub interval: Interval<usize>,
     pub children: Vec::<usize>,
-    pub parent: Option<usize>, // numerical ID of parent object
+    pub parents: Vec<usize>, // numerical IDs of parent objects
 
     pub id_str: Option<String>, // id as extracted from the attributes
-    pub parent_id_str: Option<String>, // parent as extracted from the attributes
+    pub parent_id_strs: Vec<String>, // parents as extracted from the attributes
+
+    pub origin: Option<Origin>, // set by TReader for every record it reads
 }
 
 impl Default for GffObject {
@@ -86,16 +107,20 @@ impl Default for GffObject {
             source: String::from("GANLIB"),
             g_type: Types::Unknown,
             strand: '.',
-            attrs: HashMap::new(),
+            score: None,
+            phase: None,
+            attrs: Attributes::new(),
             extra_attrs: HashMap::new(),
 
             id: None,
             interval: Interval::new(0..0).unwrap(),
             children: Vec::new(),
-            parent: None,
+            parents: Vec::new(),
 
             id_str: None,
-            parent_id_str: None,
+            parent_id_strs: Vec::new(),
+
+            origin: None,
         }
     }
 }
@@ -118,48 +143,64 @@ impl<'a> EntryT for &'a GffObject {
 
 // implementation of from for GffObject conversion from string
 impl TryFrom<(&str, bool)> for GffObject {
-    type Error = Box<dyn Error>;
+    type Error = Error;
     fn try_from((line, is_gff): (&str, bool)) -> Result<Self, Self::Error> {
         // parse line (gtf or gff)
         let mut obj = GffObject::default();
         
-        let lcs: Vec<&str> = line.split('\t').collect();
+        let lcs: Vec<&str> = line.trim_end_matches(['\n', '\r']).split('\t').collect();
         if lcs.len() != 9 {
-            Err(format!("Invalid number of columns in GFF/GTF line: {}", line).into())
+            Err(Error::ColumnCount { loc: Location::from_line(line), found: lcs.len() })
         }
         else{
             obj.seqid = lcs[0].to_string();
             obj.source = lcs[1].to_string();
-            obj.interval = Interval::new(lcs[3].parse::<usize>().unwrap()..lcs[4].parse::<usize>().unwrap())
-                    .unwrap();
-            obj.strand = lcs[6].chars().next().unwrap();
-
-            obj.g_type = match lcs[2].to_lowercase().as_str() {
-                "gene" => Types::Gene,
-                "transcript" => Types::Transcript,
-                "mrna" => Types::Transcript,
-                "exon" => Types::Exon,
-                "cds" => Types::CDS,
-                "utr" => Types::UTR,
-                "intron" => Types::Intron,
-                "intergenic" => Types::Intergenic,
-                _ => Types::Unknown,
+            let start = parse_coordinate(lcs[3], line)?;
+            let end = parse_coordinate(lcs[4], line)?;
+            obj.interval = Interval::new(start..end)
+                    .map_err(|_| Error::Coordinate { loc: Location::from_line(line), value: format!("{}-{}", start, end) })?;
+            obj.strand = match lcs[6] {
+                "+" | "-" | "." | "?" => lcs[6].chars().next().unwrap(),
+                _ => return Err(Error::Strand { loc: Location::from_line(line), value: lcs[6].to_string() }),
             };
+            obj.score = match lcs[5] {
+                "." => None,
+                score => Some(score.parse::<f32>()
+                    .map_err(|_| Error::Score { loc: Location::from_line(line), value: score.to_string() })?),
+            };
+            obj.phase = match lcs[7] {
+                "." => None,
+                "0" => Some(0),
+                "1" => Some(1),
+                "2" => Some(2),
+                phase => return Err(Error::Phase { loc: Location::from_line(line), value: phase.to_string() }),
+            };
+
+            obj.g_type = Types::from_name(lcs[2]);
 
             obj.attrs = extract_attributes(lcs[8], is_gff);
             // cleanup attributes and set id and parent if available
             obj.id_str = extract_id(&obj.attrs, &obj.g_type, is_gff);
-            obj.parent_id_str = extract_parent_id(&obj.attrs, &obj.g_type, is_gff);
+            obj.parent_id_strs = extract_parent_ids(&obj.attrs, &obj.g_type, is_gff);
 
             // add raw source information to the attributes just in case
             obj.extra_attrs = HashMap::new();
             obj.extra_attrs.insert("record_source".to_string(), lcs[2].to_string());
+            obj.extra_attrs.insert("record_format".to_string(), if is_gff { "gff" } else { "gtf" }.to_string());
+            // keep the score as written (e.g. "1.50") to print it back unchanged
+            obj.extra_attrs.insert("record_score".to_string(), lcs[5].to_string());
 
             return Ok(obj);
         }
     }
 }
 
+fn parse_coordinate(value: &str, line: &str) -> Result<usize, Error> {
+    value
+        .parse::<usize>()
+        .map_err(|_| Error::Coordinate { loc: Location::from_line(line), value: value.to_string() })
+}
+
 impl PartialEq<GffObject> for GffObject {
     fn eq(&self, other: &GffObject) -> bool {
         self.strand == other.strand()
@@ -167,6 +208,8 @@ impl PartialEq<GffObject> for GffObject {
             && self.interval == *other.interval()
             && self.source == other.source()
             && self.g_type == other.get_type()
+            && self.score == other.score()
+            && self.phase == other.phase()
             && self.attrs == *other.get_attrs()
     }
 }
@@ -175,11 +218,12 @@ impl Eq for GffObject {}
 
 impl Ord for GffObject {
     fn cmp(&self, other: &Self) -> Ordering {
-        // compare based on seqid, strand and interval
+        // compare based on seqid, interval and strand
+        // same order as coordinate-sorted files (sort -k1,1 -k4,4n -k5,5n)
         match self.seqid.cmp(&other.seqid) {
-            Ordering::Equal => match self.strand.cmp(&other.strand) {
-                Ordering::Equal => match self.interval.start.cmp(&other.interval.start) {
-                    Ordering::Equal => self.interval.end.cmp(&other.interval.end),
+            Ordering::Equal => match self.interval.start.cmp(&other.interval.start) {
+                Ordering::Equal => match self.interval.end.cmp(&other.interval.end) {
+                    Ordering::Equal => self.strand.cmp(&other.strand),
                     other => other,
                 },
                 other => other,
@@ -208,6 +252,15 @@ impl GffObjectT for GffObject {
     fn source(&self) -> &str {
         &self.source
     }
+    fn score(&self) -> Option<f32> {
+        self.score
+    }
+    fn phase(&self) -> Option<u32> {
+        self.phase
+    }
+    fn origin(&self) -> Option<&Origin> {
+        self.origin.as_ref()
+    }
     fn bed(&self) -> String {
         format!("{}\t{}\t{}\t{}\t{}\t{}",
                 self.seqid,
@@ -218,34 +271,16 @@ impl GffObjectT for GffObject {
                 self.strand)
     }
     fn gtf(&self) -> String {
-        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-                self.seqid,
-                self.source,
-                self.g_type,
-                self.interval.start,
-                self.interval.end,
-                self.score().unwrap_or(0.0),
-                self.strand,
-                self.phase().unwrap_or(0),
-                self.attrs.iter().map(|(k,v)| format!("{} \"{}\";", k, v)).collect::<Vec<String>>().join(" "))
+        self.gtf_as(None)
     }
     fn gff(&self) -> String {
-        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
-                self.seqid,
-                self.source,
-                self.g_type,
-                self.interval.start,
-                self.interval.end,
-                self.score().unwrap_or(0.0),
-                self.strand,
-                self.phase().unwrap_or(0),
-                self.attrs.iter().map(|(k,v)| format!("{}={};", k, v)).collect::<Vec<String>>().join(" "))
+        self.gff_as(None)
     }
-    fn get_attrs(&self) -> &HashMap<String, String> {
+    fn get_attrs(&self) -> &Attributes {
         &self.attrs
     }
     fn set_attr(&mut self, key: &str, value: String) {
-        self.attrs.insert(key.to_string(), value);
+        self.attrs.insert(key, value);
     }
 
     fn id(&self) -> Option<usize> {
@@ -256,6 +291,14 @@ impl GffObjectT for GffObject {
         &self.children
     }
 
+    fn parent(&self) -> Option<usize> {
+        self.parents.first().copied()
+    }
+
+    fn parents(&self) -> &[usize] {
+        &self.parents
+    }
+
     fn add_child(&mut self, child: &dyn GffObjectT) {
         // add child ID to the children vector
         
@@ -263,14 +306,101 @@ impl GffObjectT for GffObject {
     }
 
     fn set_type(&mut self, gtype: Types) {
+        // the original type name no longer applies
+        self.extra_attrs.insert("record_source".to_string(), gtype.to_string());
         self.g_type = gtype;
     }
 }
 
 impl GffObject {
-    pub fn new(line: &str, is_gff: bool) -> Result<GffObject, Box<dyn Error>> {
+    pub fn new(line: &str, is_gff: bool) -> Result<GffObject, Error> {
         GffObject::try_from((line, is_gff))
     }
+
+    // create a parent of the given type for an object which references a parent that does not exist
+    // the parent takes over the location of the child and all attributes not specific to the level of the child
+    pub fn new_parent(child: &GffObject, g_type: Types, id_str: String) -> GffObject {
+        let is_gff = child.is_gff();
+        let mut attrs = child.attrs.clone();
+        attrs.retain(|k, _| !attr_below(k, &g_type, is_gff));
+        if is_gff {
+            attrs.insert("ID", id_str.clone());
+        }
+        let parent_id_strs = extract_parent_ids(&attrs, &g_type, is_gff);
+
+        let mut extra_attrs = child.extra_attrs.clone();
+        extra_attrs.insert("record_source".to_string(), g_type.to_string());
+
+        GffObject {
+            seqid: child.seqid.clone(),
+            strand: child.strand,
+            source: child.source.clone(),
+            g_type,
+            attrs,
+            extra_attrs,
+            interval: child.interval.clone(),
+            id_str: Some(id_str),
+            parent_id_strs,
+            origin: child.origin.clone().map(|origin| Origin { line_num: 0, ..origin }),
+            ..Default::default()
+        }
+    }
+
+    // score column as written in the input unless the score has been changed since
+    fn score_str(&self) -> String {
+        match self.score {
+            None => ".".to_string(),
+            Some(score) => match self.extra_attrs.get("record_score") {
+                Some(raw) if raw.parse::<f32>().ok() == Some(score) => raw.clone(),
+                _ => score.to_string(),
+            },
+        }
+    }
+
+    fn phase_str(&self) -> String {
+        self.phase.map_or(".".to_string(), |phase| phase.to_string())
+    }
+
+    // type as written in the input (mRNA, lincRNA, Selenocysteine, ...) - the SO term name for objects created in code
+    pub fn type_name(&self) -> String {
+        self.extra_attrs.get("record_source").cloned().unwrap_or_else(|| self.g_type.to_string())
+    }
+
+    // name for the type column - the original name, or the one used by the given dialect
+    // types a dialect has no name for keep their original name
+    pub fn type_in(&self, dialect: Option<Dialect>) -> String {
+        match dialect.and_then(|dialect| self.g_type.name_in(dialect)) {
+            Some(name) => name.to_string(),
+            None => self.type_name(),
+        }
+    }
+
+    // GTF line with the type column written for the given dialect (original type if None)
+    pub fn gtf_as(&self, dialect: Option<Dialect>) -> String {
+        self.line(&self.type_in(dialect), self.attrs.gtf())
+    }
+
+    pub fn gff_as(&self, dialect: Option<Dialect>) -> String {
+        self.line(&self.type_in(dialect), self.attrs.gff())
+    }
+
+    fn line(&self, type_name: &str, attrs: String) -> String {
+        format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                self.seqid,
+                self.source,
+                type_name,
+                self.interval.start,
+                self.interval.end,
+                self.score_str(),
+                self.strand,
+                self.phase_str(),
+                attrs)
+    }
+
+    // true if the object was parsed from (or derived from a record of) a GFF file
+    pub fn is_gff(&self) -> bool {
+        self.extra_attrs.get("record_format").is_some_and(|f| f == "gff")
+    }
 }
 
 #[cfg(test)]
@@ -292,4 +422,123 @@ mod tests {
         assert_eq!(obj.attrs.get("gene_id").unwrap(), "test");
         assert_eq!(obj.attrs.get("gene_name").unwrap(), "test");
     }
+
+    #[test]
+    fn test_object_errors() {
+        let line = "chr1\ttest\tgene\t100\t200\t.\t+\t.";
+        assert!(matches!(GffObject::new(line, false), Err(Error::ColumnCount { found: 8, .. })));
+
+        let line = "chr1\ttest\tgene\t100\tabc\t.\t+\t.\tgene_id \"test\";";
+        match GffObject::new(line, false) {
+            Err(Error::Coordinate { loc, value }) => {
+                assert_eq!(value, "abc");
+                assert_eq!(loc.line, line);
+            },
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        let line = "chr1\ttest\tgene\t200\t100\t.\t+\t.\tgene_id \"test\";";
+        assert!(matches!(GffObject::new(line, false), Err(Error::Coordinate { .. })));
+
+        let line = "chr1\ttest\tgene\t100\t200\t.\tx\t.\tgene_id \"test\";";
+        assert!(matches!(GffObject::new(line, false), Err(Error::Strand { .. })));
+    }
+
+    #[test]
+    fn test_gtf_round_trip() {
+        let line = "chr1\tHAVANA\ttranscript\t11869\t14409\t.\t+\t.\tgene_id \"ENSG00000223972.5\"; transcript_id \"ENST00000456328.2\"; gene_type \"transcribed_unprocessed_pseudogene\"; level 2; tag \"basic\"; tag \"Ensembl_canonical\"; transcript_support_level \"1\";";
+        let obj = GffObject::new(line, false).unwrap();
+        assert_eq!(obj.get_attr("tag").unwrap(), "basic");
+        assert_eq!(obj.get_attrs().get_all("tag").count(), 2);
+        assert_eq!(obj.gtf(), line);
+    }
+
+    #[test]
+    fn test_feature_types() {
+        let obj = GffObject::new("chr1\tHAVANA\tSelenocysteine\t100\t102\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";", false).unwrap();
+        assert_eq!(obj.get_type(), Types::Selenocysteine);
+        assert_eq!(obj.type_name(), "Selenocysteine");
+        assert_eq!(obj.parent_id_strs, vec!["t1"]);
+
+        let mut obj = GffObject::new("chr1\ttest\tlincRNA\t100\t500\t.\t+\t.\tID=t1;Parent=g1", true).unwrap();
+        assert!(obj.get_type().is_transcript_like());
+        assert_eq!(obj.type_name(), "lincRNA");
+        obj.set_type(Types::NcRNA);
+        assert_eq!(obj.type_name(), "ncRNA");
+    }
+
+    #[test]
+    fn test_write_types() {
+        let line = "chr1\ttest\tmRNA\t100\t500\t.\t+\t.\tID=t1;Parent=g1";
+        let obj = GffObject::new(line, true).unwrap();
+        assert_eq!(obj.gff(), line);
+        assert!(obj.gtf_as(Some(Dialect::Gencode)).starts_with("chr1\ttest\ttranscript\t"));
+
+        let line = "chr1\ttest\tfive_prime_utr\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";";
+        let obj = GffObject::new(line, false).unwrap();
+        assert_eq!(obj.gtf(), line);
+        assert_eq!(obj.type_in(Some(Dialect::GTF22)), "5UTR");
+        assert_eq!(obj.type_in(Some(Dialect::Gencode)), "UTR");
+        assert_eq!(obj.type_in(Some(Dialect::GFF3)), "five_prime_UTR");
+
+        // types without a name in the dialect are written as read
+        let obj = GffObject::new("chr1\ttest\tmatch_part\t100\t200\t.\t+\t.\tID=m1", true).unwrap();
+        assert_eq!(obj.type_in(Some(Dialect::GFF3)), "match_part");
+    }
+
+    #[test]
+    fn test_score_phase() {
+        let line = "chr1\ttest\tCDS\t100\t200\t.\t+\t.\tgene_id \"g1\";";
+        let obj = GffObject::new(line, false).unwrap();
+        assert_eq!(obj.score(), None);
+        assert_eq!(obj.phase(), None);
+        assert_eq!(obj.gtf(), line);
+
+        let line = "chr1\ttest\tCDS\t100\t200\t1000.50\t+\t2\tgene_id \"g1\";";
+        let mut obj = GffObject::new(line, false).unwrap();
+        assert_eq!(obj.score(), Some(1000.5));
+        assert_eq!(obj.phase(), Some(2));
+        assert_eq!(obj.gtf(), line);
+
+        // zero is distinct from missing
+        let line = "chr1\ttest\tCDS\t100\t200\t0\t+\t0\tgene_id \"g1\";";
+        let zero = GffObject::new(line, false).unwrap();
+        assert_eq!((zero.score(), zero.phase()), (Some(0.0), Some(0)));
+        assert_eq!(zero.gtf(), line);
+
+        obj.score = Some(3.0);
+        obj.phase = None;
+        assert_eq!(obj.gtf(), "chr1\ttest\tCDS\t100\t200\t3\t+\t.\tgene_id \"g1\";");
+
+        let line = "chr1\ttest\tCDS\t100\t200\thigh\t+\t.\tgene_id \"g1\";";
+        assert!(matches!(GffObject::new(line, false), Err(Error::Score { .. })));
+        let line = "chr1\ttest\tCDS\t100\t200\t.\t+\t3\tgene_id \"g1\";";
+        assert!(matches!(GffObject::new(line, false), Err(Error::Phase { .. })));
+    }
+
+    #[test]
+    fn test_new_parent() {
+        let line = "chr1\ttest\texon\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\"; exon_number \"1\"; gene_name \"G1\";";
+        let exon = GffObject::new(line, false).unwrap();
+
+        let transcript = GffObject::new_parent(&exon, Types::Transcript, exon.parent_id_strs[0].clone());
+        assert_eq!(transcript.g_type, Types::Transcript);
+        assert_eq!(transcript.interval, exon.interval);
+        assert_eq!(transcript.id_str, Some("t1".to_string()));
+        assert_eq!(transcript.parent_id_strs, vec!["g1"]);
+        assert_eq!(transcript.attrs.len(), 3);
+        assert!(transcript.attrs.get("exon_number").is_none());
+
+        let gene = GffObject::new_parent(&transcript, Types::Gene, transcript.parent_id_strs[0].clone());
+        assert_eq!(gene.id_str, Some("g1".to_string()));
+        assert!(gene.parent_id_strs.is_empty());
+        assert_eq!(gene.attrs.len(), 2);
+        assert!(gene.attrs.get("transcript_id").is_none());
+
+        let line = "chr1\ttest\texon\t100\t200\t.\t+\t.\tID=e1;Parent=t1";
+        let exon = GffObject::new(line, true).unwrap();
+        let transcript = GffObject::new_parent(&exon, Types::Transcript, "t1".to_string());
+        assert_eq!(transcript.attrs.get("ID").unwrap(), "t1");
+        assert!(transcript.parent_id_strs.is_empty());
+    }
 }
\ No newline at end of file
@@ -1,7 +1,6 @@
-use std::collections::HashMap;
-
+use crate::attributes::Attributes;
 use crate::group::GffObjectGroupT;
-use crate::object::GffObjectT;
+use crate::object::{GffObjectT, Origin};
 use crate::utils::*;
 
 use bio::utils::Interval;
@@ -70,7 +69,7 @@ where
         self.parent.get(self.tid).unwrap().source()
     }
 
-    fn get_attrs(&self) -> &HashMap<String, String> {
+    fn get_attrs(&self) -> &Attributes {
         self.parent.get(self.tid).unwrap().get_attrs()
     }
 
@@ -92,12 +91,21 @@ where
             .get_mut(self.tid)
             .unwrap()
             .add_child(child);
+        self.parent.reset_interval(self.tid);
     }
 
     fn children(&self) -> &[usize] {
         self.parent.get(self.tid).unwrap().children()
     }
 
+    fn parent(&self) -> Option<usize> {
+        self.parent.get(self.tid).unwrap().parent()
+    }
+
+    fn parents(&self) -> &[usize] {
+        self.parent.get(self.tid).unwrap().parents()
+    }
+
     fn set_type(&mut self, gtype: Types) {
         self.parent.objects_mut().get_mut(self.tid).unwrap().set_type(gtype);
     }
@@ -121,6 +129,10 @@ where
     fn phase(&self) -> Option<u32> {
         self.parent.get(self.tid).unwrap().phase()
     }
+
+    fn origin(&self) -> Option<&Origin> {
+        self.parent.get(self.tid).unwrap().origin()
+    }
 }
 
 impl<'a, Group> TranscriptRef<'a, Group>
@@ -158,6 +170,130 @@ where
     }
 }
 
+// read-only view of a transcript and its features
+// exons, CDS and the introns and UTRs derived from them are returned in transcription order
+// (descending coordinates on the - strand), intervals use the same inclusive coordinates as the objects
+pub struct TranscriptView<'a, Group>
+where
+    Group: GffObjectGroupT,
+{
+    group: &'a Group,
+    tid: usize,
+}
+
+impl<'a, Group> TranscriptView<'a, Group>
+where
+    Group: GffObjectGroupT,
+{
+    pub fn new(group: &'a Group, tid: usize) -> Self {
+        TranscriptView { group, tid }
+    }
+
+    pub fn tid(&self) -> usize {
+        self.tid
+    }
+
+    // the transcript record itself
+    pub fn object(&self) -> &'a Group::Object {
+        self.group.get(self.tid).unwrap()
+    }
+
+    fn is_reverse(&self) -> bool {
+        self.object().strand() == '-'
+    }
+
+    // children of the given type sorted by position
+    fn children_of_type(&self, gtype: Types) -> Vec<&'a Group::Object> {
+        let group = self.group;
+        let mut children: Vec<&'a Group::Object> = self
+            .object()
+            .children()
+            .iter()
+            .filter_map(|cid| group.get(*cid))
+            .filter(|child| child.get_type() == gtype)
+            .collect();
+        children.sort_by_key(|child| (child.interval().start, child.interval().end));
+        children
+    }
+
+    // reverse intervals listed by position on the - strand
+    fn in_transcription_order<T>(&self, mut items: Vec<T>) -> Vec<T> {
+        if self.is_reverse() {
+            items.reverse();
+        }
+        items
+    }
+
+    pub fn exons(&self) -> Vec<&'a Group::Object> {
+        self.in_transcription_order(self.children_of_type(Types::Exon))
+    }
+
+    pub fn cds(&self) -> Vec<&'a Group::Object> {
+        self.in_transcription_order(self.children_of_type(Types::CDS))
+    }
+
+    pub fn exon_count(&self) -> usize {
+        self.children_of_type(Types::Exon).len()
+    }
+
+    pub fn exonic_len(&self) -> usize {
+        self.children_of_type(Types::Exon).iter().map(|exon| exon.len()).sum()
+    }
+
+    pub fn cds_len(&self) -> usize {
+        self.children_of_type(Types::CDS).iter().map(|cds| cds.len()).sum()
+    }
+
+    pub fn is_coding(&self) -> bool {
+        !self.children_of_type(Types::CDS).is_empty()
+    }
+
+    // gaps between consecutive exons - touching or overlapping exons have no intron between them
+    pub fn introns(&self) -> Vec<Interval<usize>> {
+        let exons = self.children_of_type(Types::Exon);
+        let introns = exons
+            .windows(2)
+            .filter(|pair| pair[1].interval().start > pair[0].interval().end + 1)
+            .map(|pair| Interval::new(pair[0].interval().end + 1..pair[1].interval().start - 1).unwrap())
+            .collect();
+        self.in_transcription_order(introns)
+    }
+
+    // exonic parts upstream of the CDS in transcription order - empty for non-coding transcripts
+    pub fn utr5(&self) -> Vec<Interval<usize>> {
+        let (left, right) = self.utrs();
+        self.in_transcription_order(if self.is_reverse() { right } else { left })
+    }
+
+    // exonic parts downstream of the CDS - the stop codon (not part of the CDS in GTF) is not a UTR
+    pub fn utr3(&self) -> Vec<Interval<usize>> {
+        let (left, right) = self.utrs();
+        self.in_transcription_order(if self.is_reverse() { left } else { right })
+    }
+
+    // exonic parts left and right of the CDS span (extended by the stop codon) sorted by position
+    fn utrs(&self) -> (Vec<Interval<usize>>, Vec<Interval<usize>>) {
+        let cds = self.children_of_type(Types::CDS);
+        if cds.is_empty() {
+            return (Vec::new(), Vec::new());
+        }
+        let coding = cds.into_iter().chain(self.children_of_type(Types::StopCodon));
+        let (cds_start, cds_end) = coding.fold((usize::MAX, 0), |(start, end), c| (start.min(c.interval().start), end.max(c.interval().end)));
+        let mut left = Vec::new();
+        let mut right = Vec::new();
+        for exon in self.children_of_type(Types::Exon) {
+            let (start, end) = (exon.interval().start, exon.interval().end);
+            if start < cds_start {
+                left.push(Interval::new(start..end.min(cds_start - 1)).unwrap());
+            }
+            if end > cds_end {
+                right.push(Interval::new(start.max(cds_end + 1)..end).unwrap());
+            }
+        }
+        (left, right)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,4 +311,42 @@ mod tests {
         tref.change_exon_type(Types::Other);
         println!("{:?}", tref);
     }
+
+    #[test]
+    fn test_transcript() {
+        let lines = "chr1\ttest\ttranscript\t100\t1000\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t100\t200\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t901\t1000\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t500\t600\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\tCDS\t150\t200\t.\t-\t0\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\tCDS\t500\t600\t.\t-\t2\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\tCDS\t901\t950\t.\t-\t0\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+        assert!(transcriptome.transcript(1).is_none());
+        let transcript = transcriptome.transcript(0).unwrap();
+
+        let starts = |objs: Vec<&GffObject>| objs.iter().map(|o| o.interval().start).collect::<Vec<usize>>();
+        let spans = |intervals: Vec<Interval<usize>>| intervals.iter().map(|i| (i.start, i.end)).collect::<Vec<_>>();
+        assert_eq!(starts(transcript.exons()), vec![901, 500, 100]);
+        assert_eq!(starts(transcript.cds()), vec![901, 500, 150]);
+        assert_eq!(spans(transcript.introns()), vec![(601, 900), (201, 499)]);
+        assert_eq!(spans(transcript.utr5()), vec![(951, 1000)]);
+        assert_eq!(spans(transcript.utr3()), vec![(100, 149)]);
+        assert_eq!(transcript.exon_count(), 3);
+        assert_eq!(transcript.exonic_len(), 302);
+        assert_eq!(transcript.cds_len(), 202);
+        assert!(transcript.is_coding());
+
+        // stop codons follow the CDS in GTF and are not part of the 3' UTR
+        let lines = "chr1\ttest\ttranscript\t100\t1000\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t100\t1000\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\tCDS\t200\t499\t.\t+\t0\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\tstop_codon\t500\t502\t.\t+\t0\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+        let transcript = transcriptome.transcript(0).unwrap();
+        assert_eq!(spans(transcript.utr5()), vec![(100, 199)]);
+        assert_eq!(spans(transcript.utr3()), vec![(503, 1000)]);
+    }
 }
\ No newline at end of file
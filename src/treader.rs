@@ -1,68 +1,167 @@
 use std::fs::File;
-use std::error::Error;
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::collections::{BinaryHeap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
+use std::sync::Arc;
 
-use crate::object::{GffObject, GffObjectT};
+use flate2::read::MultiGzDecoder;
+
+use crate::error::{Error, Location};
+use crate::object::{GffObject, GffObjectT, Origin};
 
 use crate::utils::*;
 
+// number of records examined to determine the format of an input
+const FORMAT_DETECTION_RECORDS: usize = 100;
+
+// comment or directive line kept while parsing
+// (##gff-version 3, ##sequence-region chr1 1 1000, ###, # free text)
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comment {
+    pub fname: String,
+    pub line_num: usize,
+    pub text: String, // full line including the leading #, without the line ending
+}
+
+impl Comment {
+    pub fn new(text: &str) -> Self {
+        Comment {
+            fname: String::new(),
+            line_num: 0,
+            text: text.trim_end_matches(['\n', '\r']).to_string(),
+        }
+    }
+
+    // directives start with ## (GFF3 pragmas) - free text comments with a single #
+    pub fn is_directive(&self) -> bool {
+        self.text.starts_with("##")
+    }
+
+    // name and value of a directive - ("gff-version", "3"), ("sequence-region", "chr1 1 1000"), ("#", "") for ###
+    pub fn directive(&self) -> Option<(&str, &str)> {
+        let body = self.text.strip_prefix("##")?;
+        match body.split_once(char::is_whitespace) {
+            Some((name, value)) => Some((name, value.trim())),
+            None => Some((body, "")),
+        }
+    }
+}
+
 // single treader - private struct to parse over a single file
 // used in TReader to parse over multiple simultaneously
-struct STReader {
-    fname: String,
-    reader: BufReader<File>,
-    comments : Vec<(u32,String)>, // (line number, comment)
+struct STReader<'a> {
+    fname: Arc<str>, // shared with the origin of every record read from the input
+    reader: Box<dyn BufRead + 'a>,
+    line_num: usize, // number of lines read so far
+    lines: VecDeque<(usize,String)>, // lines consumed while determining the format - returned before reading further
+    comments: Vec<Comment>, // comments and directives read so far and not yet taken by TReader
     is_gff: Option<bool>,
 }
 
-impl STReader{
-    pub fn new(fname: &str) -> Result<STReader,Box<dyn Error>>{
-        let file = File::open(fname)?;
-        let reader = BufReader::new(file);
+// open a file for reading - "-" stands for stdin
+fn open_reader(fname: &str) -> Result<Box<dyn BufRead>,Error> {
+    if fname == "-" {
+        return decompress(BufReader::new(std::io::stdin())).map_err(|e| Error::io(fname, e));
+    }
+    let file = File::open(fname).map_err(|e| Error::io(fname, e))?;
+    decompress(BufReader::new(file)).map_err(|e| Error::io(fname, e))
+}
+
+// transparently decompress gzip and BGZF (multi-member gzip) streams, detected by the gzip magic bytes
+fn decompress<'a, R: BufRead + 'a>(mut reader: R) -> std::io::Result<Box<dyn BufRead + 'a>> {
+    let is_gz = reader.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+    if is_gz {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(reader))))
+    } else {
+        Ok(Box::new(reader))
+    }
+}
 
+impl<'a> STReader<'a>{
+    pub fn new(fname: &str, format: Option<Format>) -> Result<STReader<'a>,Error>{
+        STReader::from_reader(fname, open_reader(fname)?, format)
+    }
+
+    // format is determined from the input unless given explicitly
+    pub fn from_reader(fname: &str, reader: Box<dyn BufRead + 'a>, format: Option<Format>) -> Result<STReader<'a>,Error>{
         // read some lines to determine if gtf or gff
-        let mut streader = STReader{fname:fname.to_string(),
+        let mut streader = STReader{fname:Arc::from(fname),
                             reader,
+                            line_num:0,
+                            lines:VecDeque::new(),
                             comments:vec![],
-                            is_gff:None};
+                            is_gff:format.map(|f| f == Format::GFF)};
         
-        let res = streader._set_gff();
-        match res {
-            Ok(_) => (),
-            Err(e) => {
-                println!("Couldn't determine the file format: {}",e);
-                return Err(e);
-            }
-        }
+        streader._set_gff()?;
         Ok(streader)
     }
 
-    fn _set_gff(&mut self) -> Result<(),Box<dyn Error>> {
-        for line in self.reader.by_ref().lines() {
-            let line = line.unwrap();
-            if line.starts_with('#') {
+    // read the next line from the underlying reader together with its line number
+    fn read_line(&mut self) -> Result<Option<(usize,String)>,Error> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Ok(None),
+            Ok(_) => {
+                self.line_num += 1;
+                Ok(Some((self.line_num,line)))
+            },
+            Err(e) => Err(Error::io(&self.fname, e)),
+        }
+    }
+
+    fn _set_gff(&mut self) -> Result<(),Error> {
+        // format set explicitly
+        if self.is_gff.is_some() {
+            return Ok(());
+        }
+
+        // each of the first records votes for GFF or GTF based on the syntax of its attributes
+        // records whose attributes fit both or neither (e.g. ".") abstain
+        // the ##gff-version pragma only decides if the records do not - it is often copied into GTF files
+        // compressed streams can not be rewound
+        // so the lines read here are kept and returned by the iterator before any new lines
+        let mut gff_votes = 0;
+        let mut gtf_votes = 0;
+        let mut pragma: Option<bool> = None;
+        let mut n_records = 0;
+        while n_records < FORMAT_DETECTION_RECORDS {
+            let (line_num,line) = match self.read_line()? {
+                Some(line) => line,
+                None => break,
+            };
+            self.lines.push_back((line_num,line));
+            let line = self.lines.back().unwrap().1.trim();
+            if let Some(version) = line.strip_prefix("##gff-version") {
+                pragma = Some(version.trim().starts_with('3'));
                 continue;
             }
-            let lcs: Vec<&str> = line.trim().split('\t').collect();
-            if lcs.len() != 9 {
-                break;
+            if line.starts_with('#') || line.is_empty() {
+                continue;
             }
-            if !["transcript", "exon", "CDS"].contains(&lcs[2]) {
-                break;
+            n_records += 1;
+            let lcs: Vec<&str> = line.split('\t').collect();
+            if lcs.len() != 9 {
+                continue;
             }
-            if lcs[2] == "transcript" {
-                self.is_gff = Some(is_gff(&line)?);
+            match attr_is_gff(lcs[8]) {
+                Ok(true) => gff_votes += 1,
+                Ok(false) => gtf_votes += 1,
+                Err(_) => (),
             }
         }
 
+        self.is_gff = match gff_votes.cmp(&gtf_votes) {
+            std::cmp::Ordering::Greater => Some(true),
+            std::cmp::Ordering::Less => Some(false),
+            std::cmp::Ordering::Equal => pragma,
+        };
+
         match self.is_gff {
             Some(_) => (),
             None => {
-                return Err("Couldn't determine the file format. No suitable lines found.".into());
+                return Err(Error::UnknownFormat{loc:Location::new(&self.fname,0,""),
+                                                reason:format!("no suitable lines found ({} GFF and {} GTF records)",gff_votes,gtf_votes)});
             }
         }
-    
-        self.reader.rewind().unwrap();
 
         Ok(())
     }
@@ -77,39 +176,72 @@ impl STReader{
     }
 }
 
-impl Iterator for STReader {
-    type Item = String;
+impl Iterator for STReader<'_> {
+    type Item = Result<(usize,String),Error>; // (line number, line)
     fn next(&mut self) -> Option<Self::Item>{
-        let mut line = String::new();
+        // comments are kept aside and empty lines are skipped
+        let is_record = |line: &str| !line.starts_with('#') && !line.trim().is_empty();
         loop {
-            match self.reader.read_line(&mut line) {
-                Ok(0) => return None,
-                Ok(_) => {
-                    if !line.starts_with('#') {
-                        return Some(line);
-                    }
-                    line.clear();
+            let (line_num, line) = match self.lines.pop_front() {
+                Some(line) => line,
+                None => match self.read_line() {
+                    Ok(None) => return None,
+                    Ok(Some(line)) => line,
+                    Err(e) => return Some(Err(e)),
                 },
-                Err(_) => return None,
+            };
+            if is_record(&line) {
+                return Some(Ok((line_num, line)));
+            }
+            if line.starts_with('#') {
+                self.comments.push(Comment {
+                    fname: self.fname.to_string(),
+                    line_num,
+                    ..Comment::new(&line)
+                });
             }
         }
     }
 }
 
 
-pub struct TReader {
+// how TReader handles records which can not be parsed
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ParsePolicy {
+    #[default]
+    Strict,  // return the error and stop reading
+    Lenient, // skip the record - only its line number is kept in ParseStats
+    Collect, // skip the record and keep the error to be inspected after reading
+}
+
+// number of records read from and skipped in a single input
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    pub fname: String,
+    pub records: usize, // all non-comment lines, including skipped ones
+    pub skipped: usize,
+    pub skipped_lines: Vec<usize>, // line numbers of the skipped records
+}
+
+// sources added with add_reader can be borrowed from the caller (in-memory buffers, ...) for the lifetime 'a
+pub struct TReader<'a> {
     fnames: Vec<String>,
-    readers: Vec<STReader>,
+    readers: Vec<STReader<'a>>,
+    policy: ParsePolicy,
+    format: Option<Format>, // format of inputs added from now on - determined from each input if None
+    stats: Vec<ParseStats>, // one entry per reader
+    diagnostics: Vec<Error>, // errors of skipped records when using ParsePolicy::Collect
+    failed: bool, // set once an error has been returned - no further records are read
 }
 
-impl Default for TReader {
+impl Default for TReader<'_> {
     fn default() -> Self {
-        TReader{fnames:vec![],readers:vec![]}
+        TReader{fnames:vec![],readers:vec![],policy:ParsePolicy::default(),format:None,stats:vec![],diagnostics:vec![],failed:false}
     }
 }
 
-impl TReader {
-    pub fn new<A>(args: Option<A>) -> Result<TReader,Box<dyn Error>>
+impl<'a> TReader<'a> {
+    pub fn new<A>(args: Option<A>) -> Result<TReader<'a>,Error>
         where A: Into<String> + Copy
     {
         let mut t = TReader::default();
@@ -122,39 +254,264 @@ impl TReader {
         Ok(t)
     }
 
-    pub fn add(&mut self, fname: &str) -> Result<(),Box<dyn Error>>{
-        self.fnames.push(fname.to_string().clone());
-        let reader = STReader::new(fname)?;
-        self.readers.push(reader);
+    // construct from any source of GTF/GFF lines (in-memory buffer, socket, already opened file, ...)
+    pub fn from_reader<R>(reader: R) -> Result<TReader<'a>,Error>
+        where R: Read + 'a
+    {
+        let mut t = TReader::default();
+        t.add_reader("<reader>", reader)?;
+        Ok(t)
+    }
+
+    // add a file by name - "-" reads from stdin
+    pub fn add(&mut self, fname: &str) -> Result<(),Error>{
+        let reader = STReader::new(fname,self.format)?;
+        self.push_reader(reader);
 
         Ok(())
     }
+
+    // add a source of GTF/GFF lines - name is only used to identify the source
+    pub fn add_reader<R>(&mut self, name: &str, reader: R) -> Result<(),Error>
+        where R: Read + 'a
+    {
+        let reader = decompress(BufReader::new(reader)).map_err(|e| Error::io(name, e))?;
+        let reader = STReader::from_reader(name, reader, self.format)?;
+        self.push_reader(reader);
+
+        Ok(())
+    }
+
+    fn push_reader(&mut self, reader: STReader<'a>) {
+        self.fnames.push(reader.fname.to_string());
+        self.stats.push(ParseStats{fname:reader.fname.to_string(),..Default::default()});
+        self.readers.push(reader);
+    }
+
+    // set the format of inputs added afterwards instead of detecting it from their contents
+    pub fn set_format(&mut self, format: Option<Format>) {
+        self.format = format;
+    }
+
+    pub fn set_policy(&mut self, policy: ParsePolicy) {
+        self.policy = policy;
+    }
+
+    pub fn policy(&self) -> ParsePolicy {
+        self.policy
+    }
+
+    // per-input counts of records read and skipped so far
+    pub fn stats(&self) -> &[ParseStats] {
+        &self.stats
+    }
+
+    // errors of all records skipped so far under ParsePolicy::Collect
+    pub fn diagnostics(&self) -> &[Error] {
+        &self.diagnostics
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    // comments and directives of all inputs read so far in the order they were read
+    // each comment is returned before the record that follows it in the input
+    pub fn comments(&self) -> impl Iterator<Item = &Comment> {
+        self.readers.iter().flat_map(|r| r.comments.iter())
+    }
+
+    // remove and return the comments read so far - used to place comments relative to the records
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        self.readers.iter_mut().flat_map(|r| std::mem::take(&mut r.comments)).collect()
+    }
 }
 
-impl Iterator for TReader {
-    type Item = GffObject;
+impl<'a> TReader<'a> {
+    // names of the inputs in the order they were added - indexed by the input number returned by merged()
+    pub fn fnames(&self) -> &[String] {
+        &self.fnames
+    }
+
+    // merge already sorted inputs into a single stream ordered by GffObject's Ord (seqid, start, end, strand)
+    // inputs must be sorted with seqids in lexicographic order (sort -k1,1 -k4,4n)
+    // each record is returned together with the number of the input it was read from
+    pub fn merged(self) -> MergedTReader<'a> {
+        let n = self.readers.len();
+        MergedTReader{reader:self,heads:BinaryHeap::new(),last:vec![None;n],pending:None,started:false}
+    }
+
+    // handle an error of a record from input idx according to the policy
+    // returns the error if reading has to stop
+    fn reject(&mut self, idx: usize, e: Error) -> Option<Error> {
+        let line_num = e.location().map_or(0, |loc| loc.line_num);
+        match self.policy {
+            ParsePolicy::Strict => {
+                self.failed = true;
+                return Some(e);
+            },
+            ParsePolicy::Lenient => (),
+            ParsePolicy::Collect => {
+                self.diagnostics.push(e);
+            },
+        }
+        self.stats[idx].skipped += 1;
+        self.stats[idx].skipped_lines.push(line_num);
+        None
+    }
+
+    // next record of input idx together with its line number and raw line
+    fn next_record(&mut self, idx: usize) -> Option<Result<(usize,String,GffObject),Error>> {
+        if self.failed {
+            return None;
+        }
+        loop {
+            let reader = &mut self.readers[idx];
+            let (line_num,l) = match reader.next()? {
+                Ok(line) => line,
+                Err(e) => {
+                    // I/O errors leave the input in an unknown state - never skipped
+                    self.failed = true;
+                    return Some(Err(e));
+                },
+            };
+            self.stats[idx].records += 1;
+            let e = match GffObject::new(l.as_str(),reader.is_gff()) {
+                Ok(mut robj) => {
+                    robj.origin = Some(Origin{input:idx,fname:reader.fname.clone(),line_num});
+                    return Some(Ok((line_num,l,robj)));
+                },
+                Err(e) => e.at(&reader.fname,line_num),
+            };
+            if let Some(e) = self.reject(idx,e) {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+impl Iterator for TReader<'_> {
+    type Item = Result<GffObject,Error>;
     fn next(&mut self) -> Option<Self::Item>{
         // iterate over readers
-        for (i,reader) in self.readers.iter_mut().enumerate() {
-            // if reader is not empty, return line
-            if let Some(l) = reader.next() {
-                
-                let robj = match GffObject::new(l.as_str(),reader.is_gff()) {
-                    Ok(robj) => robj,
-                    Err(e) => {panic!("Error parsing line: {}",e);},
-                };
-                return Some(robj);
+        for idx in 0..self.readers.len() {
+            // if reader is not empty, return record
+            if let Some(res) = self.next_record(idx) {
+                return Some(res.map(|(_,_,obj)| obj));
             }
         }
         None
     }
 }
 
+// current record of an input waiting to be merged
+// ordered in reverse so that BinaryHeap returns the smallest record first - ties go to the earlier input
+struct MergeHead {
+    obj: GffObject,
+    idx: usize,
+}
+
+impl PartialEq for MergeHead {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == std::cmp::Ordering::Equal
+    }
+}
+
+impl Eq for MergeHead {}
+
+impl Ord for MergeHead {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        other.obj.cmp(&self.obj).then_with(|| other.idx.cmp(&self.idx))
+    }
+}
+
+impl PartialOrd for MergeHead {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// k-way merge over the inputs of a TReader - see TReader::merged
+// records which are out of order within their input are errors handled according to the parse policy
+pub struct MergedTReader<'a> {
+    reader: TReader<'a>,
+    heads: BinaryHeap<MergeHead>, // at most one record per input
+    last: Vec<Option<(String,usize)>>, // seqid and start of the last record read from each input
+    pending: Option<Error>, // error raised while refilling the heap - returned after the record already taken
+    started: bool,
+}
+
+impl<'a> MergedTReader<'a> {
+    // underlying reader - for stats, diagnostics, comments and input names
+    pub fn reader(&self) -> &TReader<'a> {
+        &self.reader
+    }
+
+    pub fn take_comments(&mut self) -> Vec<Comment> {
+        self.reader.take_comments()
+    }
+
+    pub fn take_diagnostics(&mut self) -> Vec<Error> {
+        self.reader.take_diagnostics()
+    }
+
+    // read the next record of input idx into the heap
+    fn fill(&mut self, idx: usize) -> Option<Error> {
+        loop {
+            let (line_num,line,obj) = match self.reader.next_record(idx)? {
+                Ok(rec) => rec,
+                Err(e) => return Some(e),
+            };
+            let key = (obj.seqid.clone(),obj.interval.start);
+            match &self.last[idx] {
+                Some(prev) if key < *prev => {
+                    let e = Error::Unsorted{loc:Location::new(&self.reader.fnames[idx],line_num,&line),
+                                            previous:format!("{}:{}",prev.0,prev.1)};
+                    if let Some(e) = self.reader.reject(idx,e) {
+                        return Some(e);
+                    }
+                },
+                _ => {
+                    self.last[idx] = Some(key);
+                    self.heads.push(MergeHead{obj,idx});
+                    return None;
+                },
+            }
+        }
+    }
+}
+
+impl Iterator for MergedTReader<'_> {
+    type Item = Result<(usize,GffObject),Error>; // (input number, record)
+    fn next(&mut self) -> Option<Self::Item>{
+        if let Some(e) = self.pending.take() {
+            return Some(Err(e));
+        }
+        if !self.started {
+            self.started = true;
+            for idx in 0..self.reader.readers.len() {
+                if let Some(e) = self.fill(idx) {
+                    return Some(Err(e));
+                }
+            }
+        }
+        if self.reader.failed {
+            return None;
+        }
+        let head = self.heads.pop()?;
+        // replace the record with the next one from the same input
+        self.pending = self.fill(head.idx);
+        Some(Ok((head.idx,head.obj)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::fs::File;
     use std::io::Write;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
 
     #[test]
     fn test_streader() {
@@ -168,7 +525,7 @@ mod tests {
         writeln!(file,"chr1\ttest\texon\t1\t100\t.\t+\t.\ttranscript_id=transcript2").unwrap();
         file.flush().unwrap();
 
-        let mut reader = STReader::new(fname).unwrap();
+        let mut reader = STReader::new(fname,None).unwrap();
         assert_eq!(reader.is_gff(),true);
 
         // remove file
@@ -203,6 +560,217 @@ mod tests {
         std::fs::remove_file("test2.gff").unwrap();
     }
 
+    #[test]
+    fn test_treader_gzip() {
+        let lines = "##gff-version 3\n\
+                     chr1\ttest\ttranscript\t1\t100\t.\t+\t.\tID=transcript1\n\
+                     chr1\ttest\texon\t1\t100\t.\t+\t.\tID=exon1;Parent=transcript1\n";
+
+        // plain gzip
+        let fname = "gzip_test.gff.gz";
+        let mut encoder = GzEncoder::new(File::create(fname).unwrap(), Compression::default());
+        encoder.write_all(lines.as_bytes()).unwrap();
+        encoder.finish().unwrap();
+
+        let treader = TReader::new(Some(fname)).unwrap();
+        let objs: Vec<GffObject> = treader.collect::<Result<_,_>>().unwrap();
+        assert_eq!(objs.len(), 2);
+        assert_eq!(objs[1].get_type(), Types::Exon);
+        std::fs::remove_file(fname).unwrap();
+
+        // BGZF files consist of several concatenated gzip members
+        let fname = "bgzf_test.gff.gz";
+        let mut file = File::create(fname).unwrap();
+        for line in lines.split_inclusive('\n') {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(line.as_bytes()).unwrap();
+            file.write_all(&encoder.finish().unwrap()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let treader = TReader::new(Some(fname)).unwrap();
+        assert_eq!(treader.count(), 2);
+        std::fs::remove_file(fname).unwrap();
+    }
+
+    #[test]
+    fn test_treader_from_reader() {
+        let lines = "# comment\n\
+                     chr1\ttest\ttranscript\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        let treader = TReader::from_reader(lines.as_bytes()).unwrap();
+        let objs: Vec<GffObject> = treader.collect::<Result<_,_>>().unwrap();
+        assert_eq!(objs.len(), 2);
+        assert_eq!(objs[0].get_type(), Types::Transcript);
+
+        let mut treader = TReader::default();
+        treader.add_reader("first", lines.as_bytes()).unwrap();
+        treader.add_reader("second", std::io::Cursor::new(lines.as_bytes().to_vec())).unwrap();
+        assert_eq!(treader.count(), 4);
+
+        // buffers owned by the caller are borrowed for as long as the reader lives
+        let owned = lines.to_string();
+        let treader = TReader::from_reader(owned.as_bytes()).unwrap();
+        assert_eq!(treader.count(), 2);
+    }
+
+    #[test]
+    fn test_treader_errors() {
+        let lines = "chr1\ttest\ttranscript\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     # comment\n\
+                     chr1\ttest\texon\t1\tx\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t1\t100\t.\t+\n";
+        let mut treader = TReader::default();
+        treader.add_reader("broken.gtf", lines.as_bytes()).unwrap();
+        assert!(treader.next().unwrap().is_ok());
+        match treader.next().unwrap() {
+            Err(Error::Coordinate { loc, value }) => {
+                assert_eq!(value, "x");
+                assert_eq!(loc.fname, "broken.gtf");
+                assert_eq!(loc.line_num, 3);
+                assert!(loc.line.starts_with("chr1\ttest\texon"));
+            },
+            res => panic!("unexpected result: {:?}", res),
+        }
+        // strict parsing stops at the first error
+        assert!(treader.next().is_none());
+
+        assert!(matches!(TReader::new(Some("missing.gtf")), Err(Error::Io { .. })));
+    }
+
+    #[test]
+    fn test_treader_policies() {
+        let lines = "chr1\ttest\ttranscript\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t1\tx\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t1\t100\t.\t+\n\
+                     chr1\ttest\texon\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n";
+
+        let mut treader = TReader::default();
+        treader.set_policy(ParsePolicy::Lenient);
+        treader.add_reader("first.gtf", lines.as_bytes()).unwrap();
+        treader.add_reader("second.gtf", lines.as_bytes()).unwrap();
+        let objs: Vec<GffObject> = treader.by_ref().collect::<Result<_,_>>().unwrap();
+        assert_eq!(objs.len(), 4);
+        assert_eq!(treader.stats(), &[ParseStats{fname:"first.gtf".to_string(),records:4,skipped:2,skipped_lines:vec![2,3]},
+                                      ParseStats{fname:"second.gtf".to_string(),records:4,skipped:2,skipped_lines:vec![2,3]}]);
+        assert!(treader.diagnostics().is_empty());
+
+        let mut treader = TReader::default();
+        treader.set_policy(ParsePolicy::Collect);
+        treader.add_reader("first.gtf", lines.as_bytes()).unwrap();
+        assert_eq!(treader.by_ref().filter(|r| r.is_ok()).count(), 2);
+        let diagnostics = treader.take_diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert!(matches!(diagnostics[0], Error::Coordinate { .. }));
+        assert_eq!(diagnostics[1].location().unwrap().line_num, 3);
+        assert_eq!(treader.stats()[0].skipped, 2);
+    }
+
+    #[test]
+    fn test_format_detection() {
+        let detect = |lines: &'static str| {
+            STReader::from_reader("test", Box::new(lines.as_bytes()), None).map(|mut r| r.is_gff())
+        };
+
+        // GFF3 starting with gene and mRNA records
+        let lines = "chr1\ttest\tgene\t1\t100\t.\t+\t.\tID=g1;Name=G1\n\
+                     chr1\ttest\tmRNA\t1\t100\t.\t+\t.\tID=t1;Parent=g1\n";
+        assert!(detect(lines).unwrap());
+
+        // GTF starting with a gene record and GENCODE-style unquoted values
+        let lines = "chr1\ttest\tgene\t1\t100\t.\t+\t.\tgene_id \"g1\"; level 2;\n\
+                     chr1\ttest\tstart_codon\t1\t3\t.\t+\t0\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        assert!(!detect(lines).unwrap());
+
+        // exon-only GTF with a misleading pragma
+        let lines = "##gff-version 3\n\
+                     chr1\ttest\texon\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        assert!(!detect(lines).unwrap());
+
+        // pragma decides when the records do not
+        let lines = "##gff-version 3\n\
+                     chr1\ttest\tregion\t1\t100\t.\t+\t.\t.\n";
+        assert!(detect(lines).unwrap());
+
+        // explicit format when nothing can be determined
+        let lines = "chr1\ttest\tregion\t1\t100\t.\t+\t.\t.\n";
+        assert!(matches!(detect(lines), Err(Error::UnknownFormat { .. })));
+        let mut treader = TReader::default();
+        treader.set_format(Some(Format::GFF));
+        treader.add_reader("test", lines.as_bytes()).unwrap();
+        assert_eq!(treader.count(), 1);
+    }
+
+    #[test]
+    fn test_comments() {
+        let lines = "##gff-version 3\n\
+                     ##sequence-region chr1 1 1000\n\
+                     # free text\n\
+                     chr1\ttest\tgene\t1\t100\t.\t+\t.\tID=g1\n\
+                     ###\n\
+                     chr1\ttest\tgene\t200\t300\t.\t+\t.\tID=g2\n";
+        let mut treader = TReader::default();
+        treader.add_reader("test.gff", lines.as_bytes()).unwrap();
+
+        assert!(treader.next().unwrap().is_ok());
+        let comments = treader.take_comments();
+        assert_eq!(comments.len(), 3);
+        assert_eq!(comments[0].directive(), Some(("gff-version", "3")));
+        assert_eq!(comments[1].directive(), Some(("sequence-region", "chr1 1 1000")));
+        assert_eq!(comments[1].line_num, 2);
+        assert_eq!(comments[1].fname, "test.gff");
+        assert!(!comments[2].is_directive());
+        assert_eq!(comments[2].text, "# free text");
+
+        assert!(treader.next().unwrap().is_ok());
+        let comments: Vec<&Comment> = treader.comments().collect();
+        assert_eq!(comments.len(), 1);
+        assert_eq!(comments[0].directive(), Some(("#", "")));
+        assert_eq!(comments[0].line_num, 5);
+    }
+
+    #[test]
+    fn test_treader_merged() {
+        let first = "chr1\ttest\texon\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr1\ttest\texon\t300\t400\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                     chr2\ttest\texon\t50\t80\t.\t-\t.\tgene_id \"g3\"; transcript_id \"t3\";\n";
+        let second = "chr1\ttest\texon\t200\t250\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t2\";\n\
+                      chr1\ttest\texon\t300\t350\t.\t-\t.\tgene_id \"g2\"; transcript_id \"t2\";\n\
+                      chr10\ttest\texon\t1\t10\t.\t+\t.\tgene_id \"g4\"; transcript_id \"t4\";\n";
+        let mut treader = TReader::default();
+        treader.add_reader("first.gtf", first.as_bytes()).unwrap();
+        treader.add_reader("second.gtf", second.as_bytes()).unwrap();
+        let merged = treader.merged();
+        assert_eq!(merged.reader().fnames(), &["first.gtf".to_string(), "second.gtf".to_string()]);
+        let recs: Vec<(usize,GffObject)> = merged.collect::<Result<_,_>>().unwrap();
+        let order: Vec<(usize,&str,usize)> = recs.iter().map(|(i,o)| (*i,o.seqid.as_str(),o.interval.start)).collect();
+        assert_eq!(order, vec![(0,"chr1",1),(1,"chr1",200),(1,"chr1",300),(0,"chr1",300),
+                               (1,"chr10",1),(0,"chr2",50)]);
+
+        // inputs which are not sorted are reported
+        let unsorted = "chr1\ttest\texon\t300\t400\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n\
+                        chr1\ttest\texon\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        let mut treader = TReader::default();
+        treader.add_reader("unsorted.gtf", unsorted.as_bytes()).unwrap();
+        let mut merged = treader.merged();
+        assert!(merged.next().unwrap().is_ok());
+        match merged.next().unwrap() {
+            Err(Error::Unsorted { loc, previous }) => {
+                assert_eq!(loc.line_num, 2);
+                assert_eq!(previous, "chr1:300");
+            },
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(merged.next().is_none());
+
+        let mut treader = TReader::default();
+        treader.set_policy(ParsePolicy::Collect);
+        treader.add_reader("unsorted.gtf", unsorted.as_bytes()).unwrap();
+        let mut merged = treader.merged();
+        assert_eq!(merged.by_ref().count(), 1);
+        assert_eq!(merged.take_diagnostics().len(), 1);
+    }
+
     #[test]
     fn test_streader_with_invalid_format() {
         let fname = "invalid.gff";
@@ -211,7 +779,7 @@ mod tests {
         writeln!(file, "another invalid line").unwrap();
         file.flush().unwrap();
 
-        let reader_result = STReader::new(fname);
+        let reader_result = STReader::new(fname,None);
         assert!(reader_result.is_err(), "Invalid file format should return an error");
 
         std::fs::remove_file(fname).unwrap();
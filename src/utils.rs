@@ -1,15 +1,40 @@
-use std::collections::HashMap;
+use crate::attributes::Attributes;
 use std::fmt::{Formatter, Display};
-use std::error::Error;
 
+use crate::error::{Error, Location};
+
+// feature types modelled after the Sequence Ontology (SO)
+// the type column is matched case-insensitively against the SO term names and common synonyms (Types::from_name)
+// the original string is kept by GffObject (GffObject::type_name) so that records are written back unchanged
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum Types {
+    // gene-like
     Gene,
+    Pseudogene,
+    NcRNAGene,
+    // transcript-like
     Transcript,
+    MRNA,
+    LncRNA,
+    NcRNA,
+    MiRNA,
+    SnRNA,
+    SnoRNA,
+    RRNA,
+    TRNA,
+    PrimaryTranscript,
+    PseudogenicTranscript,
+    // parts of transcripts
     Exon,
     CDS,
     UTR,
+    FivePrimeUTR,
+    ThreePrimeUTR,
+    StartCodon,
+    StopCodon,
+    Selenocysteine,
     Intron,
+    // outside of the gene hierarchy
     Intergenic,
     Other,
     Unknown,
@@ -18,40 +43,203 @@ pub enum Types {
 impl Display for Types {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", match self {
-            Types::Gene => "gene",
-            Types::Transcript => "transcript",
-            Types::Exon => "exon",
-            Types::CDS => "CDS",
-            Types::UTR => "UTR",
-            Types::Intron => "intron",
-            Types::Intergenic => "intergenic",
             Types::Other => "other",
             Types::Unknown => "unknown",
+            _ => self.so_term().unwrap(),
         })
     }
 }
 
+// output conventions for the type column
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dialect {
+    GTF22,   // GTF2.2 - 5UTR, 3UTR, inter
+    Gencode, // GENCODE/Ensembl GTF - gene, transcript and UTR for both ends
+    GFF3,    // Sequence Ontology term names
+}
+
+// input format of an annotation file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    GTF,
+    GFF,
+}
+
+impl Types {
+    // parse the type column - unrecognized types are Unknown
+    pub fn from_name(name: &str) -> Types {
+        match name.to_lowercase().as_str() {
+            "gene" => Types::Gene,
+            "pseudogene" => Types::Pseudogene,
+            "ncrna_gene" => Types::NcRNAGene,
+            "transcript" => Types::Transcript,
+            "mrna" | "messenger_rna" => Types::MRNA,
+            "lnc_rna" | "lncrna" | "lincrna" => Types::LncRNA,
+            "ncrna" | "noncoding_transcript" => Types::NcRNA,
+            "mirna" | "microrna" => Types::MiRNA,
+            "snrna" => Types::SnRNA,
+            "snorna" => Types::SnoRNA,
+            "rrna" => Types::RRNA,
+            "trna" => Types::TRNA,
+            "primary_transcript" => Types::PrimaryTranscript,
+            "pseudogenic_transcript" => Types::PseudogenicTranscript,
+            "exon" => Types::Exon,
+            "cds" | "coding_sequence" => Types::CDS,
+            "utr" | "untranslated_region" => Types::UTR,
+            "five_prime_utr" | "5utr" | "5'utr" | "utr5" | "five_prime_untranslated_region" => Types::FivePrimeUTR,
+            "three_prime_utr" | "3utr" | "3'utr" | "utr3" | "three_prime_untranslated_region" => Types::ThreePrimeUTR,
+            "start_codon" => Types::StartCodon,
+            "stop_codon" => Types::StopCodon,
+            "selenocysteine" | "stop_codon_redefined_as_selenocysteine" => Types::Selenocysteine,
+            "intron" => Types::Intron,
+            "intergenic" | "intergenic_region" => Types::Intergenic,
+            _ => Types::Unknown,
+        }
+    }
+
+    // name of the Sequence Ontology term - None for types without one
+    pub fn so_term(&self) -> Option<&'static str> {
+        match self {
+            Types::Gene => Some("gene"),
+            Types::Pseudogene => Some("pseudogene"),
+            Types::NcRNAGene => Some("ncRNA_gene"),
+            Types::Transcript => Some("transcript"),
+            Types::MRNA => Some("mRNA"),
+            Types::LncRNA => Some("lnc_RNA"),
+            Types::NcRNA => Some("ncRNA"),
+            Types::MiRNA => Some("miRNA"),
+            Types::SnRNA => Some("snRNA"),
+            Types::SnoRNA => Some("snoRNA"),
+            Types::RRNA => Some("rRNA"),
+            Types::TRNA => Some("tRNA"),
+            Types::PrimaryTranscript => Some("primary_transcript"),
+            Types::PseudogenicTranscript => Some("pseudogenic_transcript"),
+            Types::Exon => Some("exon"),
+            Types::CDS => Some("CDS"),
+            Types::UTR => Some("UTR"),
+            Types::FivePrimeUTR => Some("five_prime_UTR"),
+            Types::ThreePrimeUTR => Some("three_prime_UTR"),
+            Types::StartCodon => Some("start_codon"),
+            Types::StopCodon => Some("stop_codon"),
+            Types::Selenocysteine => Some("stop_codon_redefined_as_selenocysteine"),
+            Types::Intron => Some("intron"),
+            Types::Intergenic => Some("intergenic_region"),
+            Types::Other | Types::Unknown => None,
+        }
+    }
+
+    // name of the type in the given output dialect - None if the dialect has no name for it (Other, Unknown)
+    pub fn name_in(&self, dialect: Dialect) -> Option<&'static str> {
+        match dialect {
+            Dialect::GFF3 => self.so_term(),
+            Dialect::GTF22 | Dialect::Gencode => {
+                if self.is_gene_like() {
+                    return Some("gene");
+                }
+                if self.is_transcript_like() {
+                    return Some("transcript");
+                }
+                match (self, dialect) {
+                    (Types::FivePrimeUTR, Dialect::GTF22) => Some("5UTR"),
+                    (Types::ThreePrimeUTR, Dialect::GTF22) => Some("3UTR"),
+                    (Types::Intergenic, Dialect::GTF22) => Some("inter"),
+                    (Types::FivePrimeUTR | Types::ThreePrimeUTR, _) => Some("UTR"),
+                    (Types::Selenocysteine, _) => Some("Selenocysteine"),
+                    (Types::Intergenic, _) => Some("intergenic"),
+                    _ => self.so_term(),
+                }
+            }
+        }
+    }
+
+    // top level of the hierarchy
+    pub fn is_gene_like(&self) -> bool {
+        matches!(self, Types::Gene | Types::Pseudogene | Types::NcRNAGene)
+    }
+
+    // children of genes and parents of exons, CDS, UTRs, ...
+    pub fn is_transcript_like(&self) -> bool {
+        matches!(
+            self,
+            Types::Transcript
+                | Types::MRNA
+                | Types::LncRNA
+                | Types::NcRNA
+                | Types::MiRNA
+                | Types::SnRNA
+                | Types::SnoRNA
+                | Types::RRNA
+                | Types::TRNA
+                | Types::PrimaryTranscript
+                | Types::PseudogenicTranscript
+        )
+    }
+
+    // features located on a transcript
+    pub fn is_transcript_part(&self) -> bool {
+        matches!(
+            self,
+            Types::Exon
+                | Types::CDS
+                | Types::UTR
+                | Types::FivePrimeUTR
+                | Types::ThreePrimeUTR
+                | Types::StartCodon
+                | Types::StopCodon
+                | Types::Selenocysteine
+                | Types::Intron
+        )
+    }
+
+    // true if both types are at the same level of the gene->transcript->feature hierarchy
+    pub fn same_level(&self, other: &Types) -> bool {
+        self == other
+            || (self.is_gene_like() && other.is_gene_like())
+            || (self.is_transcript_like() && other.is_transcript_like())
+    }
+
+    // type expected of the parent of an object of this type in a gene->transcript->feature hierarchy
+    // also the type of parents synthesized for objects whose parent is missing
+    pub fn parent_type(&self) -> Option<Types> {
+        if self.is_transcript_like() {
+            Some(Types::Gene)
+        } else if self.is_transcript_part() {
+            Some(Types::Transcript)
+        } else {
+            None
+        }
+    }
+}
+
 impl Default for Types {
     fn default() -> Self {
         Types::Unknown
     }
 }
 
-pub fn extract_attributes(attr_str: &str, is_gff: bool) -> HashMap<String, String> {
-    let mut attrs = HashMap::new();
+// parse the 9th column keeping the order, case and repetitions of keys
+pub fn extract_attributes(attr_str: &str, is_gff: bool) -> Attributes {
+    let mut attrs = Attributes::new();
 
     if is_gff {
         for pair in attr_str.split(';').map(str::trim).filter(|s| !s.is_empty()) {
+            // any attribute can hold several comma-separated values - literal commas are escaped as %2C
             if let Some((key, value)) = pair.split_once('=') {
-                attrs.insert(key.to_lowercase().to_string(), value.to_string());
+                for value in value.split(',') {
+                    attrs.push(key, percent_decode(value));
+                }
             }
         }
     } else {
         for pair in attr_str.split(';').map(str::trim).filter(|s| !s.is_empty()) {
             let mut parts = pair.splitn(2, ' ');
             if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
-                let value = value.trim_matches('"');
-                attrs.insert(key.to_lowercase().to_string(), value.to_string());
+                let value = value.trim();
+                if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+                    attrs.push(key, value[1..value.len()-1].to_string());
+                } else {
+                    attrs.push_unquoted(key, value.to_string());
+                }
             }
         }
     }
@@ -59,16 +247,18 @@ pub fn extract_attributes(attr_str: &str, is_gff: bool) -> HashMap<String, Strin
     attrs
 }
 
-pub fn extract_id(attrs: &HashMap<String, String>, feature_type: &Types, is_gff: bool) -> Option<String> {
+pub fn extract_id(attrs: &Attributes, feature_type: &Types, is_gff: bool) -> Option<String> {
     if is_gff {
-        if let Some(id) = attrs.get("id") {
+        if let Some(id) = attrs.get("ID") {
             return Some(id.clone());
         }
     } else {
-        let id_key = match feature_type {
-            Types::Gene => Some("gene_id"),
-            Types::Transcript => Some("transcript_id"),
-            _ => None,
+        let id_key = if feature_type.is_gene_like() {
+            Some("gene_id")
+        } else if feature_type.is_transcript_like() {
+            Some("transcript_id")
+        } else {
+            None
         };
         if let Some(key) = id_key {
             if let Some(id) = attrs.get(key) {
@@ -79,28 +269,80 @@ pub fn extract_id(attrs: &HashMap<String, String>, feature_type: &Types, is_gff:
     None
 }
 
-pub fn extract_parent_id(attrs: &HashMap<String, String>, feature_type: &Types, is_gff: bool) -> Option<String> {
-    if is_gff {
-        if let Some(parent) = attrs.get("parent") {
-            return Some(parent.clone());
+// decode %XX escapes of GFF3 attribute values (%3B -> ;)
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded: Vec<u8> = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit() && bytes[i + 2].is_ascii_hexdigit() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap();
+            decoded.push(u8::from_str_radix(hex, 16).unwrap());
+            i += 3;
+            continue;
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+// escape characters with reserved meaning in GFF3 attribute values as %XX
+pub fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' | '=' | '&' | ',' | '%' => encoded.push_str(&format!("%{:02X}", c as u32)),
+            c if c.is_ascii_control() => encoded.push_str(&format!("%{:02X}", c as u32)),
+            c => encoded.push(c),
         }
+    }
+    encoded
+}
+
+// first parent of the object - see extract_parent_ids
+pub fn extract_parent_id(attrs: &Attributes, feature_type: &Types, is_gff: bool) -> Option<String> {
+    extract_parent_ids(attrs, feature_type, is_gff).into_iter().next()
+}
+
+// all parents of the object - GFF3 records may have several (Parent=tx1,tx2)
+pub fn extract_parent_ids(attrs: &Attributes, feature_type: &Types, is_gff: bool) -> Vec<String> {
+    if is_gff {
+        return attrs.get_all("Parent").cloned().collect();
     } else {
-        let parent_id_key = match feature_type {
-            Types::Gene => None,
-            Types::Transcript => Some("gene_id"),
-            Types::Exon | Types::CDS | Types::Intron | Types::UTR => Some("transcript_id"),
-            _ => Some("transcript_id"),
+        let parent_id_key = if feature_type.is_gene_like() {
+            None
+        } else if feature_type.is_transcript_like() {
+            Some("gene_id")
+        } else {
+            Some("transcript_id")
         };
         if let Some(key) = parent_id_key {
             if let Some(parent) = attrs.get(key) {
-                return Some(parent.clone());
+                return vec![parent.clone()];
             }
         }
     }
-    None
+    Vec::new()
 }
 
-fn get_attr_value(attrs: &HashMap<String, String>, keys: &[&str]) -> Option<String> {
+// attributes which describe the level of the hierarchy below the given type
+// and should not be propagated upwards when creating a parent object of that type
+pub fn attr_below(key: &str, feature_type: &Types, is_gff: bool) -> bool {
+    if is_gff && (key == "ID" || key == "Parent") {
+        return true;
+    }
+    if feature_type.is_gene_like() {
+        key.starts_with("transcript_") || key.starts_with("exon_")
+    } else if feature_type.is_transcript_like() {
+        key.starts_with("exon_")
+    } else {
+        false
+    }
+}
+
+fn get_attr_value(attrs: &Attributes, keys: &[&str]) -> Option<String> {
     let mut value = None;
     for key in keys {
         if let Some(v) = attrs.get(*key) {
@@ -117,53 +359,91 @@ fn get_attr_value(attrs: &HashMap<String, String>, keys: &[&str]) -> Option<Stri
     value
 }
 
-pub fn attr_is_gff(attrs: &str) -> Result<bool,Box<dyn Error>> {
+pub fn attr_is_gff(attrs: &str) -> Result<bool,Error> {
     // remove any trailing whitespace and last ; if present
     // split by ; and remove any leading or trailing whitespace
     let attrs_vec: Vec<&str> = attrs.trim().trim_end_matches(';').split(';').map(str::trim).collect();
 
+    // GFF pairs are key=value with no spaces or quotes in the key
+    // GTF pairs are key followed by a space and a (possibly unquoted - level 2;) value
+    let is_gff_pair = |a: &str| match a.split_once('=') {
+        Some((k, _)) => !k.is_empty() && !k.contains(' ') && !k.contains('"'),
+        None => false,
+    };
+    let is_gtf_pair = |a: &str| match a.split_once(' ') {
+        Some((k, v)) => !k.contains('=') && !v.trim().is_empty(),
+        None => false,
+    };
+
     // if any attribute string begins with ID= or Parent=
+    // or the attributes can only be read as GFF
     // and every attribute string has "="
     // then is GFF
-    let is_gff = attrs_vec.
-        iter().
-        any(|a| a.starts_with("ID=") || a.starts_with("Parent=")) && attrs_vec.iter().all(|a| a.contains('='));
-
-    if is_gff {
+    let all_gff = attrs_vec.iter().all(|a| is_gff_pair(a));
+    let all_gtf = attrs_vec.iter().all(|a| is_gtf_pair(a));
+    let has_gff_ids = attrs_vec.iter().any(|a| a.starts_with("ID=") || a.starts_with("Parent="));
+    if all_gff && (has_gff_ids || !all_gtf) {
         return Ok(true);
     }
     // if not GFF - need to validate GTF
-    // make sure each pair has at least one space followed by \" and ending with \"
-    let is_gtf = attrs_vec.iter().all(|a| a.contains(" \"") && a.ends_with("\""));
-    if is_gtf {
+    if all_gtf {
         return Ok(false);
     }
     else{
-        return Err("Cannot determine format".into());
+        return Err(Error::UnknownFormat{loc:Location::default(),reason:format!("attributes are neither GFF nor GTF: {}",attrs)});
     }
 }
 
 // given a gtf/gff line - determine format
-pub fn is_gff(line: &str) -> Result<bool,Box<dyn Error>> { // return true if GFF, false if GTF
+pub fn is_gff(line: &str) -> Result<bool,Error> { // return true if GFF, false if GTF
     // if line starts with # - ERror
     if line.starts_with('#') {
-       return Err("Cannot determine format from comment line".into());
+       return Err(Error::UnknownFormat{loc:Location::from_line(line),reason:"comment line".to_string()});
     }
 
     // split line by tab
     let lcs: Vec<&str> = line.trim().split('\t').collect();
     // if not 9 columns - return error
     if lcs.len() != 9 {
-        return Err("Invalid number of columns".into());
+        return Err(Error::ColumnCount{loc:Location::from_line(line),found:lcs.len()});
     }
 
-    attr_is_gff(lcs[8])
+    attr_is_gff(lcs[8]).map_err(|e| match e {
+        Error::UnknownFormat{reason,..} => Error::UnknownFormat{loc:Location::from_line(line),reason},
+        e => e,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_types() {
+        assert_eq!(Types::from_name("mRNA"), Types::MRNA);
+        assert_eq!(Types::from_name("lincRNA"), Types::LncRNA);
+        assert_eq!(Types::from_name("five_prime_utr"), Types::FivePrimeUTR);
+        assert_eq!(Types::from_name("Selenocysteine"), Types::Selenocysteine);
+        assert_eq!(Types::from_name("match_part"), Types::Unknown);
+        assert_eq!(Types::LncRNA.to_string(), "lnc_RNA");
+
+        assert!(Types::Pseudogene.is_gene_like());
+        assert!(Types::PseudogenicTranscript.is_transcript_like());
+        assert!(Types::StopCodon.is_transcript_part());
+        assert!(Types::MRNA.same_level(&Types::Transcript));
+        assert!(!Types::MRNA.same_level(&Types::Gene));
+        assert_eq!(Types::NcRNA.parent_type(), Some(Types::Gene));
+        assert_eq!(Types::StartCodon.parent_type(), Some(Types::Transcript));
+        assert_eq!(Types::Gene.parent_type(), None);
+
+        assert_eq!(Types::MRNA.name_in(Dialect::Gencode), Some("transcript"));
+        assert_eq!(Types::MRNA.name_in(Dialect::GFF3), Some("mRNA"));
+        assert_eq!(Types::FivePrimeUTR.name_in(Dialect::GTF22), Some("5UTR"));
+        assert_eq!(Types::FivePrimeUTR.name_in(Dialect::Gencode), Some("UTR"));
+        assert_eq!(Types::Selenocysteine.name_in(Dialect::GFF3), Some("stop_codon_redefined_as_selenocysteine"));
+        assert_eq!(Types::Unknown.name_in(Dialect::GFF3), None);
+    }
+
     #[test]
     fn test_extract_attributes() {
         let gff_line = "ID=gene1; gene_name=GENE1";
@@ -175,6 +455,52 @@ mod tests {
         assert_eq!(gtf_attrs.len(), 2);
     }
 
+    #[test]
+    fn test_extract_attributes_lossless() {
+        let gtf_line = "gene_id \"ENSG00000223972.5\"; transcript_id \"ENST00000456328.2\"; level 2; tag \"basic\"; tag \"CCDS\"; transcript_support_level \"1\";";
+        let gtf_attrs = extract_attributes(gtf_line,false);
+        assert_eq!(gtf_attrs.len(), 6);
+        assert_eq!(gtf_attrs.get_all("tag").collect::<Vec<_>>(), vec!["basic", "CCDS"]);
+        assert_eq!(gtf_attrs.gtf(), gtf_line);
+
+        let gff_line = "ID=gene1;Parent=tx1;Name=GENE1";
+        let gff_attrs = extract_attributes(gff_line,true);
+        assert_eq!(gff_attrs.keys(), vec!["ID", "Parent", "Name"]);
+        assert_eq!(extract_id(&gff_attrs, &Types::Exon, true), Some("gene1".to_string()));
+        assert_eq!(extract_parent_id(&gff_attrs, &Types::Exon, true), Some("tx1".to_string()));
+    }
+
+    #[test]
+    fn test_gff_multi_value_attributes() {
+        let gff_line = "ID=exon1;Parent=tx1,tx2;Dbxref=GeneID:1,HGNC:2;Note=a%3Bb%3Dc%2Cd%25";
+        let gff_attrs = extract_attributes(gff_line,true);
+        assert_eq!(gff_attrs.get_all("Parent").collect::<Vec<_>>(), vec!["tx1", "tx2"]);
+        assert_eq!(gff_attrs.get_all("Dbxref").count(), 2);
+        assert_eq!(gff_attrs.get("Note").unwrap(), "a;b=c,d%");
+        assert_eq!(extract_parent_ids(&gff_attrs, &Types::Exon, true), vec!["tx1", "tx2"]);
+        assert_eq!(gff_attrs.gff(), gff_line);
+
+        // not limited to the attributes the spec defines as multi-valued
+        let gff_attrs = extract_attributes("ID=gene1;Alias=x,y;Note=a,b%2Cc", true);
+        assert_eq!(gff_attrs.get_all("Alias").collect::<Vec<_>>(), vec!["x", "y"]);
+        assert_eq!(gff_attrs.get_all("Note").collect::<Vec<_>>(), vec!["a", "b,c"]);
+        assert_eq!(gff_attrs.gff(), "ID=gene1;Alias=x,y;Note=a,b%2Cc");
+
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_encode("a\tb"), "a%09b");
+    }
+
+    #[test]
+    fn test_attr_is_gff() {
+        assert!(attr_is_gff("ID=gene1;Name=GENE1").unwrap());
+        assert!(attr_is_gff("Name=GENE1;gene_biotype=protein_coding").unwrap());
+        assert!(attr_is_gff("Note=text with spaces").unwrap());
+        assert!(!attr_is_gff("gene_id \"g1\"; transcript_id \"t1\";").unwrap());
+        assert!(!attr_is_gff("gene_id \"g1\"; level 2; note \"a=b\";").unwrap());
+        assert!(attr_is_gff("gene_id").is_err());
+        assert!(attr_is_gff("").is_err());
+    }
+
     #[test]
     fn test_extract_ids() {
         let gff_line = "ID=gene1; gene_name=GENE1";
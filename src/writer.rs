@@ -0,0 +1,837 @@
+// writers for a whole Transcriptome
+// records are written hierarchically - each gene followed by its transcripts, each transcript followed by its features
+
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+use crate::attributes::Attributes;
+use crate::error::Error;
+use crate::group::{GffObjectGroupT, Transcriptome};
+use crate::object::{GffObject, GffObjectT};
+use crate::treader::Comment;
+use crate::utils::*;
+
+// file (or stdout) opened by the create() constructors of the writers
+// finish() flushes it and completes the gzip stream - dropping it instead silently ignores errors of the last write
+pub struct Output {
+    fname: String,
+    sink: Sink,
+}
+
+enum Sink {
+    Plain(BufWriter<Box<dyn Write>>),
+    Gzip(BufWriter<GzEncoder<File>>),
+}
+
+impl Output {
+    // "-" stands for stdout, names ending with .gz are gzip compressed
+    pub fn create(fname: &str) -> Result<Output, Error> {
+        let sink = if fname == "-" {
+            Sink::Plain(BufWriter::new(Box::new(std::io::stdout())))
+        } else {
+            let file = File::create(fname).map_err(|e| Error::io(fname, e))?;
+            match fname.ends_with(".gz") {
+                true => Sink::Gzip(BufWriter::new(GzEncoder::new(file, Compression::default()))),
+                false => Sink::Plain(BufWriter::new(Box::new(file))),
+            }
+        };
+        Ok(Output { fname: fname.to_string(), sink })
+    }
+
+    pub fn finish(self) -> Result<(), Error> {
+        let fname = self.fname;
+        match self.sink {
+            Sink::Plain(mut writer) => writer.flush().map_err(|e| Error::io(&fname, e)),
+            Sink::Gzip(writer) => {
+                let encoder = writer.into_inner().map_err(|e| Error::io(&fname, e.into_error()))?;
+                encoder.finish().map_err(|e| Error::io(&fname, e))?;
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match &mut self.sink {
+            Sink::Plain(writer) => writer.write(buf),
+            Sink::Gzip(writer) => writer.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.sink {
+            Sink::Plain(writer) => writer.flush(),
+            Sink::Gzip(writer) => writer.flush(),
+        }
+    }
+}
+
+// true if the object was read from the given input (any object if None)
+fn is_from(obj: &GffObject, input: Option<usize>) -> bool {
+    input.is_none_or(|input| obj.origin.as_ref().map(|origin| origin.input) == Some(input))
+}
+
+// top-level objects sorted by position - genes before other records at the same position
+// when writing a single input, objects whose parents all belong to other inputs are top-level as well
+fn sorted_roots(transcriptome: &Transcriptome, input: Option<usize>) -> Vec<usize> {
+    let mut roots: Vec<&GffObject> = transcriptome
+        .objects()
+        .into_iter()
+        .filter(|obj| is_from(obj, input))
+        .filter(|obj| obj.parents.iter().all(|pid| transcriptome.get(*pid).is_none_or(|parent| !is_from(parent, input))))
+        .collect();
+    roots.sort_by(|a, b| a.cmp(b).then_with(|| level_rank(&a.g_type).cmp(&level_rank(&b.g_type))).then_with(|| a.id.cmp(&b.id)));
+    roots.into_iter().map(|obj| obj.id.unwrap()).collect()
+}
+
+fn level_rank(gtype: &Types) -> usize {
+    if gtype.is_gene_like() {
+        0
+    } else if gtype.is_transcript_like() {
+        1
+    } else {
+        2
+    }
+}
+
+// order of features starting at the same position of a transcript (as in GENCODE) - exons before the features within them
+fn feature_rank(gtype: &Types) -> usize {
+    match gtype {
+        Types::Exon => 0,
+        Types::CDS => 1,
+        Types::StartCodon => 2,
+        Types::StopCodon => 3,
+        Types::Selenocysteine => 4,
+        Types::UTR | Types::FivePrimeUTR | Types::ThreePrimeUTR => 5,
+        _ => 6,
+    }
+}
+
+// children of an object in output order
+// features of transcripts follow the direction of transcription, everything else is sorted by position
+fn ordered_children(transcriptome: &Transcriptome, oid: usize, input: Option<usize>) -> Vec<usize> {
+    let obj = transcriptome.get(oid).unwrap();
+    let mut children: Vec<&GffObject> = obj
+        .children
+        .iter()
+        .filter_map(|cid| transcriptome.get(*cid))
+        .filter(|child| is_from(child, input))
+        .collect();
+    if obj.g_type.is_transcript_like() && obj.strand == '-' {
+        children.sort_by_key(|c| (Reverse(c.interval.end), feature_rank(&c.g_type), Reverse(c.interval.start), c.id));
+    } else {
+        children.sort_by_key(|c| (c.interval.start, level_rank(&c.g_type), feature_rank(&c.g_type), c.interval.end, c.id));
+    }
+    children.into_iter().map(|c| c.id.unwrap()).collect()
+}
+
+// objects in output order with the parent each one is written under (None for top-level objects)
+// objects with several parents are written once - under the first parent reached
+fn walk(transcriptome: &Transcriptome, input: Option<usize>) -> Vec<(usize, Option<usize>)> {
+    let mut order = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(usize, Option<usize>)> = sorted_roots(transcriptome, input).into_iter().rev().map(|oid| (oid, None)).collect();
+    while let Some((oid, via)) = stack.pop() {
+        if !seen.insert(oid) {
+            continue;
+        }
+        order.push((oid, via));
+        stack.extend(ordered_children(transcriptome, oid, input).into_iter().rev().map(|cid| (cid, Some(oid))));
+    }
+    order
+}
+
+// GFF3 has no quoting - keys written without quotes in the GTF a GFF3 file was converted from are listed in this pragma
+// (#!gtf-unquoted level exon_number) so that converting back to GTF restores them
+const UNQUOTED_PRAGMA: &str = "#!gtf-unquoted";
+
+// pragmas the writers generate themselves instead of copying them from the input
+fn is_generated(comment: &Comment) -> bool {
+    matches!(comment.directive(), Some(("gff-version", _)) | Some(("sequence-region", _)) | Some(("#", _)))
+        || comment.text.starts_with(UNQUOTED_PRAGMA)
+}
+
+// keys listed in the UNQUOTED_PRAGMA of the inputs
+fn pragma_unquoted_keys(transcriptome: &Transcriptome) -> Vec<String> {
+    let mut keys: Vec<String> = Vec::new();
+    for (_, comment) in transcriptome.comments() {
+        if let Some(listed) = comment.text.strip_prefix(UNQUOTED_PRAGMA) {
+            for key in listed.split_whitespace() {
+                if !keys.iter().any(|k| k == key) {
+                    keys.push(key.to_string());
+                }
+            }
+        }
+    }
+    keys
+}
+
+// comments and directives of the input placed relative to the records written
+// records are reordered on output - a comment stays with the top-level object whose subtree holds the record it preceded
+struct PlacedComments<'a> {
+    header: Vec<&'a Comment>,                      // before the first record of the input - written at the top
+    before_root: HashMap<usize, Vec<&'a Comment>>, // written before the top-level object
+    trailer: Vec<&'a Comment>,                     // after the last record of the last input
+}
+
+impl<'a> PlacedComments<'a> {
+    fn new(transcriptome: &'a Transcriptome, order: &[(usize, Option<usize>)], input: Option<usize>) -> Self {
+        let mut root_of: HashMap<usize, usize> = HashMap::new();
+        let mut root = 0;
+        for (oid, via) in order {
+            if via.is_none() {
+                root = *oid;
+            }
+            root_of.insert(*oid, root);
+        }
+        let first = order.iter().map(|(oid, _)| *oid).min();
+        let last_input = input.is_none_or(|input| input + 1 == transcriptome.inputs().len());
+
+        let mut placed = PlacedComments { header: Vec::new(), before_root: HashMap::new(), trailer: Vec::new() };
+        for (before, comment) in transcriptome.comments() {
+            if is_generated(comment) {
+                continue;
+            }
+            // comments at the end of the input point past its last record - at an object synthesized by finalize if any
+            let at_end = transcriptome.get(*before).is_none_or(|obj| obj.origin.as_ref().is_some_and(|origin| origin.line_num == 0));
+            if at_end {
+                if last_input {
+                    placed.trailer.push(comment);
+                }
+            } else if Some(*before) == first {
+                placed.header.push(comment);
+            } else if let Some(root) = root_of.get(before) {
+                placed.before_root.entry(*root).or_default().push(comment);
+            }
+        }
+        placed
+    }
+}
+
+fn write_comments<W: Write>(writer: &mut W, comments: &[&Comment]) -> Result<(), Error> {
+    for comment in comments {
+        writeln!(writer, "{}", comment.text)?;
+    }
+    Ok(())
+}
+
+// ID of an object in GTF terms - gene_id of genes and transcript_id of transcripts, falling back to the GFF3 ID
+fn gtf_id(obj: &GffObject, key: &str) -> Option<String> {
+    obj.attrs.get(key).cloned().or_else(|| obj.id_str.clone())
+}
+
+// gene_id and transcript_id of the ancestors of an object written without its parents (parents from other inputs)
+fn ancestor_ids(transcriptome: &Transcriptome, oid: usize) -> (Option<String>, Option<String>) {
+    let mut gene_id = None;
+    let mut transcript_id = None;
+    let mut seen = HashSet::from([oid]);
+    let mut parent = transcriptome.get(oid).and_then(|obj| obj.parents.first().copied());
+    while let Some(obj) = parent.filter(|pid| seen.insert(*pid)).and_then(|pid| transcriptome.get(pid)) {
+        if obj.g_type.is_gene_like() {
+            gene_id = gene_id.or_else(|| gtf_id(obj, "gene_id"));
+        } else if obj.g_type.is_transcript_like() {
+            gene_id = gene_id.or_else(|| obj.attrs.get("gene_id").cloned());
+            transcript_id = transcript_id.or_else(|| gtf_id(obj, "transcript_id"));
+        }
+        parent = obj.parents.first().copied();
+    }
+    (gene_id, transcript_id)
+}
+
+// writes a Transcriptome as GTF
+// gene_id and transcript_id are the first attributes of every record - taken from the hierarchy for records read from GFF3
+// records GTF can not represent (region, chromosome, ... - no gene_id or transcript_id) are skipped, their children are still written
+// the type column is written as read unless a dialect is set (see GffObject::type_in)
+pub struct GtfWriter<W: Write> {
+    writer: W,
+    dialect: Option<Dialect>,
+    exon_number: bool, // add exon_number to exons and the features within them
+    input: Option<usize>,
+    origin_label: Option<String>,
+}
+
+impl GtfWriter<Output> {
+    // write to a file - "-" writes to stdout, names ending with .gz are gzip compressed
+    pub fn create(fname: &str) -> Result<Self, Error> {
+        Ok(GtfWriter::new(Output::create(fname)?))
+    }
+
+    // flush the file and complete its gzip stream
+    pub fn finish(self) -> Result<(), Error> {
+        self.writer.finish()
+    }
+}
+
+impl<W: Write> GtfWriter<W> {
+    pub fn new(writer: W) -> Self {
+        GtfWriter { writer, dialect: None, exon_number: false, input: None, origin_label: None }
+    }
+
+    pub fn set_dialect(&mut self, dialect: Option<Dialect>) {
+        self.dialect = dialect;
+    }
+
+    pub fn set_exon_number(&mut self, exon_number: bool) {
+        self.exon_number = exon_number;
+    }
+
+    // write only the records read from one input (index into Transcriptome::inputs)
+    pub fn set_input(&mut self, input: Option<usize>) {
+        self.input = input;
+    }
+
+    // add the name of the input of each record as an attribute (as Transcriptome::label_origin, without modifying the records)
+    pub fn set_origin_label(&mut self, key: Option<&str>) {
+        self.origin_label = key.map(str::to_string);
+    }
+
+    // underlying writer - e.g. the buffer written to, or a GzEncoder passed to new() which has to be finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn write(&mut self, transcriptome: &Transcriptome) -> Result<(), Error> {
+        let order = walk(transcriptome, self.input);
+        let comments = PlacedComments::new(transcriptome, &order, self.input);
+        let unquoted = pragma_unquoted_keys(transcriptome);
+        write_comments(&mut self.writer, &comments.header)?;
+        for (oid, via) in &order {
+            if via.is_none() {
+                if let Some(before_root) = comments.before_root.get(oid) {
+                    write_comments(&mut self.writer, before_root)?;
+                }
+                let (gene_id, transcript_id) = ancestor_ids(transcriptome, *oid);
+                self.write_object(transcriptome, *oid, gene_id, transcript_id, None, &unquoted)?;
+            }
+        }
+        write_comments(&mut self.writer, &comments.trailer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    // write an object and its descendants
+    // gene_id and transcript_id are passed down from the parents the object is written under
+    // so that features shared by several transcripts are written once per transcript with the right IDs
+    fn write_object(
+        &mut self,
+        transcriptome: &Transcriptome,
+        oid: usize,
+        gene_id: Option<String>,
+        transcript_id: Option<String>,
+        exon_number: Option<usize>,
+        unquoted: &[String],
+    ) -> Result<(), Error> {
+        let obj = transcriptome.get(oid).unwrap();
+        let (gene_id, transcript_id) = if obj.g_type.is_gene_like() {
+            (gtf_id(obj, "gene_id"), None)
+        } else if obj.g_type.is_transcript_like() {
+            (obj.attrs.get("gene_id").cloned().or(gene_id), gtf_id(obj, "transcript_id"))
+        } else {
+            (gene_id.or_else(|| obj.attrs.get("gene_id").cloned()), transcript_id.or_else(|| obj.attrs.get("transcript_id").cloned()))
+        };
+
+        let mut attrs = obj.attrs.clone();
+        if obj.is_gff() {
+            attrs.retain(|k, _| k != "ID" && k != "Parent");
+            for key in unquoted {
+                attrs.set_quoted(key, false);
+            }
+        }
+        if let Some(gene_id) = &gene_id {
+            attrs.insert("gene_id", gene_id.clone());
+        }
+        if let Some(transcript_id) = &transcript_id {
+            attrs.insert("transcript_id", transcript_id.clone());
+        }
+        attrs.move_to_front(&["gene_id", "transcript_id"]);
+        if let Some(exon_number) = exon_number {
+            match attrs.contains_key("exon_number") {
+                true => attrs.insert("exon_number", exon_number.to_string()),
+                false => attrs.push_unquoted("exon_number", exon_number.to_string()),
+            }
+        }
+        if let (Some(key), Some(origin)) = (&self.origin_label, &obj.origin) {
+            attrs.insert(key, origin.fname.to_string());
+        }
+        if gene_id.is_some() && (obj.g_type.is_gene_like() || transcript_id.is_some()) {
+            let mut record = obj.clone();
+            record.attrs = attrs;
+            writeln!(self.writer, "{}", record.gtf_as(self.dialect))?;
+        }
+
+        // features are numbered after the exon they lie in, counted in the direction of transcription
+        let exons = match self.exon_number && obj.g_type.is_transcript_like() {
+            true => transcriptome.transcript(oid).map(|t| t.exons()).unwrap_or_default(),
+            false => Vec::new(),
+        };
+        for cid in ordered_children(transcriptome, oid, self.input) {
+            let child = transcriptome.get(cid).unwrap();
+            let exon_number = exons.iter().position(|exon| exon.contains(child)).map(|n| n + 1);
+            self.write_object(transcriptome, cid, gene_id.clone(), transcript_id.clone(), exon_number, unquoted)?;
+        }
+        Ok(())
+    }
+}
+
+// writes a Transcriptome as GFF3
+// every record gets an ID - IDs missing from the input (exons and CDS of GTF files, ...) are generated from the ID of the parent
+// and the position of the record among the features of the same type, in the order they are written (t1.exon1, t1.exon2, ...)
+// attributes other than ID and Parent are kept as they are (gene_id and transcript_id of GTF records included)
+// repeated GTF keys (tag "basic"; tag "CCDS";) become one multi-valued attribute (tag=basic,CCDS)
+pub struct Gff3Writer<W: Write> {
+    writer: W,
+    dialect: Option<Dialect>,
+    input: Option<usize>,
+    origin_label: Option<String>,
+}
+
+impl Gff3Writer<Output> {
+    // write to a file - "-" writes to stdout, names ending with .gz are gzip compressed
+    pub fn create(fname: &str) -> Result<Self, Error> {
+        Ok(Gff3Writer::new(Output::create(fname)?))
+    }
+
+    // flush the file and complete its gzip stream
+    pub fn finish(self) -> Result<(), Error> {
+        self.writer.finish()
+    }
+}
+
+impl<W: Write> Gff3Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Gff3Writer { writer, dialect: None, input: None, origin_label: None }
+    }
+
+    pub fn set_dialect(&mut self, dialect: Option<Dialect>) {
+        self.dialect = dialect;
+    }
+
+    // write only the records read from one input (index into Transcriptome::inputs)
+    pub fn set_input(&mut self, input: Option<usize>) {
+        self.input = input;
+    }
+
+    // add the name of the input of each record as an attribute (as Transcriptome::label_origin, without modifying the records)
+    pub fn set_origin_label(&mut self, key: Option<&str>) {
+        self.origin_label = key.map(str::to_string);
+    }
+
+    // underlying writer - e.g. the buffer written to, or a GzEncoder passed to new() which has to be finished
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn write(&mut self, transcriptome: &Transcriptome) -> Result<(), Error> {
+        let order = walk(transcriptome, self.input);
+        let ids = self.assign_ids(transcriptome, &order);
+        let comments = PlacedComments::new(transcriptome, &order, self.input);
+
+        writeln!(self.writer, "##gff-version 3")?;
+        write_comments(&mut self.writer, &comments.header)?;
+        let unquoted = unquoted_keys(transcriptome, &order);
+        if !unquoted.is_empty() {
+            writeln!(self.writer, "{} {}", UNQUOTED_PRAGMA, unquoted.join(" "))?;
+        }
+        for region in sequence_regions(transcriptome, &order) {
+            writeln!(self.writer, "##sequence-region {}", region)?;
+        }
+
+        for (idx, (oid, via)) in order.iter().enumerate() {
+            if via.is_none() {
+                if idx > 0 {
+                    writeln!(self.writer, "###")?;
+                }
+                if let Some(before_root) = comments.before_root.get(oid) {
+                    write_comments(&mut self.writer, before_root)?;
+                }
+            }
+            let obj = transcriptome.get(*oid).unwrap();
+            let mut attrs = Attributes::new();
+            attrs.push("ID", ids[oid].clone());
+            // parents not written (other inputs) are left out
+            for parent_id in obj.parents.iter().filter_map(|pid| ids.get(pid)) {
+                attrs.push("Parent", parent_id.clone());
+            }
+            for (key, value) in &obj.attrs {
+                if key != "ID" && key != "Parent" {
+                    attrs.push(key, value.to_string());
+                }
+            }
+            if let (Some(key), Some(origin)) = (&self.origin_label, &obj.origin) {
+                attrs.insert(key, origin.fname.to_string());
+            }
+            let mut record = obj.clone();
+            record.attrs = attrs;
+            writeln!(self.writer, "{}", record.gff_as(self.dialect))?;
+        }
+        write_comments(&mut self.writer, &comments.trailer)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    // GFF3 ID of every object
+    // IDs from the input are kept - records of an input sharing ID, type and parents are one multi-line feature (CDS segments)
+    // and keep the ID together, other IDs used more than once (gene_id equal to transcript_id, ...) get a _2, _3, ... suffix
+    fn assign_ids(&self, transcriptome: &Transcriptome, order: &[(usize, Option<usize>)]) -> HashMap<usize, String> {
+        let reserved: HashSet<&str> = transcriptome.objects().iter().filter_map(|obj| obj.id_str.as_deref()).collect();
+        let mut taken: HashSet<String> = HashSet::new();
+        let mut features: HashMap<(String, String, Vec<usize>, Option<usize>), String> = HashMap::new();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut ids: HashMap<usize, String> = HashMap::new();
+        for (oid, via) in order {
+            let obj = transcriptome.get(*oid).unwrap();
+            let id = match &obj.id_str {
+                Some(id_str) => {
+                    let feature = (id_str.clone(), obj.type_name(), obj.parents.clone(), obj.origin.as_ref().map(|origin| origin.input));
+                    match features.get(&feature) {
+                        Some(id) => id.clone(),
+                        None => {
+                            let id = match taken.contains(id_str) {
+                                true => unique_id(id_str, &taken, &reserved),
+                                false => id_str.clone(),
+                            };
+                            features.insert(feature, id.clone());
+                            id
+                        }
+                    }
+                }
+                None => {
+                    let type_name = obj.type_in(self.dialect);
+                    let prefix = match via.and_then(|pid| ids.get(&pid)) {
+                        Some(parent_id) => format!("{}.{}", parent_id, type_name),
+                        None => type_name,
+                    };
+                    let count = counts.entry(prefix.clone()).or_insert(0);
+                    *count += 1;
+                    unique_id(&format!("{}{}", prefix, count), &taken, &reserved)
+                }
+            };
+            taken.insert(id.clone());
+            ids.insert(*oid, id);
+        }
+        ids
+    }
+}
+
+// keys written without quotes by GTF records, or listed as such by the input
+fn unquoted_keys(transcriptome: &Transcriptome, order: &[(usize, Option<usize>)]) -> Vec<String> {
+    let mut keys = pragma_unquoted_keys(transcriptome);
+    for (oid, _) in order {
+        let obj = transcriptome.get(*oid).unwrap();
+        if obj.is_gff() {
+            continue;
+        }
+        for key in obj.attrs.unquoted_keys() {
+            if !keys.iter().any(|k| k == key) {
+                keys.push(key.to_string());
+            }
+        }
+    }
+    keys
+}
+
+// base itself if free, otherwise the first base_2, base_3, ... neither written yet nor used by any record of the input
+fn unique_id(base: &str, taken: &HashSet<String>, reserved: &HashSet<&str>) -> String {
+    if !taken.contains(base) && !reserved.contains(base) {
+        return base.to_string();
+    }
+    (2..)
+        .map(|n| format!("{}_{}", base, n))
+        .find(|id| !taken.contains(id) && !reserved.contains(id.as_str()))
+        .unwrap()
+}
+
+// values of the ##sequence-region pragmas - taken from the input, or spanning 1 to the last base annotated on the sequence
+fn sequence_regions(transcriptome: &Transcriptome, order: &[(usize, Option<usize>)]) -> Vec<String> {
+    let mut regions: Vec<String> = Vec::new();
+    let mut seqids: HashSet<String> = HashSet::new();
+    for value in transcriptome.directives("sequence-region") {
+        if let Some(seqid) = value.split_whitespace().next() {
+            if seqids.insert(seqid.to_string()) {
+                regions.push(value.to_string());
+            }
+        }
+    }
+    let mut ends: Vec<(String, usize)> = Vec::new();
+    for (oid, _) in order {
+        let obj = transcriptome.get(*oid).unwrap();
+        if seqids.contains(&obj.seqid) {
+            continue;
+        }
+        match ends.iter_mut().find(|(seqid, _)| *seqid == obj.seqid) {
+            Some((_, end)) => *end = (*end).max(obj.interval.end),
+            None => ends.push((obj.seqid.clone(), obj.interval.end)),
+        }
+    }
+    regions.extend(ends.into_iter().map(|(seqid, end)| format!("{} 1 {}", seqid, end)));
+    regions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::treader::TReader;
+
+    fn write_gtf(transcriptome: &Transcriptome, exon_number: bool) -> Vec<String> {
+        let mut writer = GtfWriter::new(Vec::new());
+        writer.set_exon_number(exon_number);
+        writer.write(transcriptome).unwrap();
+        String::from_utf8(writer.into_inner()).unwrap().lines().map(String::from).collect()
+    }
+
+    #[test]
+    fn test_gtf_writer() {
+        let lines = "##description: test\n\
+                     chr1\ttest\texon\t100\t200\t.\t-\t.\ttranscript_id \"t1\"; gene_id \"g1\";\n\
+                     chr1\ttest\tCDS\t150\t200\t.\t-\t1\ttranscript_id \"t1\"; gene_id \"g1\";\n\
+                     chr1\ttest\texon\t300\t400\t.\t-\t.\ttranscript_id \"t1\"; gene_id \"g1\"; level 2;\n\
+                     chr1\ttest\tCDS\t300\t350\t.\t-\t0\ttranscript_id \"t1\"; gene_id \"g1\";\n\
+                     chr1\ttest\ttranscript\t100\t400\t.\t-\t.\ttranscript_id \"t1\"; gene_id \"g1\";\n\
+                     chr1\ttest\ttranscript\t10\t50\t.\t+\t.\tgene_id \"g0\"; transcript_id \"t0\";\n\
+                     chr1\ttest\texon\t10\t50\t.\t+\t.\tgene_id \"g0\"; transcript_id \"t0\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+
+        let out = write_gtf(&transcriptome, true);
+        let expected = vec![
+            "##description: test",
+            "chr1\ttest\tgene\t10\t50\t.\t+\t.\tgene_id \"g0\";",
+            "chr1\ttest\ttranscript\t10\t50\t.\t+\t.\tgene_id \"g0\"; transcript_id \"t0\";",
+            "chr1\ttest\texon\t10\t50\t.\t+\t.\tgene_id \"g0\"; transcript_id \"t0\"; exon_number 1;",
+            "chr1\ttest\tgene\t100\t400\t.\t-\t.\tgene_id \"g1\";",
+            "chr1\ttest\ttranscript\t100\t400\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\";",
+            "chr1\ttest\texon\t300\t400\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\"; level 2; exon_number 1;",
+            "chr1\ttest\tCDS\t300\t350\t.\t-\t0\tgene_id \"g1\"; transcript_id \"t1\"; exon_number 1;",
+            "chr1\ttest\texon\t100\t200\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\"; exon_number 2;",
+            "chr1\ttest\tCDS\t150\t200\t.\t-\t1\tgene_id \"g1\"; transcript_id \"t1\"; exon_number 2;",
+        ];
+        assert_eq!(out, expected);
+
+        // the written file reads back into the same records
+        let path = std::env::temp_dir().join(format!("gtf_writer_test_{}.gtf.gz", std::process::id()));
+        let fname = path.to_str().unwrap();
+        let mut writer = GtfWriter::create(fname).unwrap();
+        writer.write(&transcriptome).unwrap();
+        writer.finish().unwrap();
+        let reread = Transcriptome::from_file(fname);
+        std::fs::remove_file(fname).unwrap();
+        let mut reread = reread.unwrap();
+        reread.finalize().unwrap();
+        assert_eq!(write_gtf(&reread, true), expected);
+    }
+
+    #[test]
+    fn test_gtf_writer_from_gff() {
+        let lines = "chr1\ttest\tregion\t1\t1000\t.\t+\t.\tID=chr1\n\
+                     chr1\ttest\tgene\t100\t400\t.\t+\t.\tID=g1;Name=G1\n\
+                     chr1\ttest\tmRNA\t100\t400\t.\t+\t.\tID=t1;Parent=g1\n\
+                     chr1\ttest\tmRNA\t100\t400\t.\t+\t.\tID=t2;Parent=g1\n\
+                     chr1\ttest\texon\t100\t400\t.\t+\t.\tID=e1;Parent=t1,t2\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+
+        let mut writer = GtfWriter::new(Vec::new());
+        writer.set_dialect(Some(Dialect::Gencode));
+        writer.write(&transcriptome).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        let out: Vec<&str> = out.lines().collect();
+        // the region has no gene_id and is not written
+        assert_eq!(out.len(), 5);
+        assert_eq!(out[0], "chr1\ttest\tgene\t100\t400\t.\t+\t.\tgene_id \"g1\"; Name \"G1\";");
+        assert_eq!(out[1], "chr1\ttest\ttranscript\t100\t400\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";");
+        // the shared exon is written for each of its transcripts
+        assert_eq!(out[2], "chr1\ttest\texon\t100\t400\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";");
+        assert_eq!(out[4], "chr1\ttest\texon\t100\t400\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t2\";");
+    }
+
+    fn two_inputs() -> Transcriptome {
+        let first = "##description: first\n\
+                     chr1\ttest\ttranscript\t500\t600\t.\t+\t.\tgene_id \"g2\"; transcript_id \"t2\";\n\
+                     ##species: human\n\
+                     # novel\n\
+                     chr1\ttest\ttranscript\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";\n";
+        let second = "#!genome-build b\n\
+                      chr2\ttest\ttranscript\t1\t50\t.\t+\t.\tgene_id \"g3\"; transcript_id \"t3\";\n\
+                      # end of b\n";
+        let mut reader = TReader::default();
+        reader.add_reader("a.gtf", first.as_bytes()).unwrap();
+        reader.add_reader("b.gtf", second.as_bytes()).unwrap();
+        let mut transcriptome = Transcriptome::new();
+        transcriptome.add_from_treader(&mut reader).unwrap();
+        transcriptome.finalize().unwrap();
+        transcriptome
+    }
+
+    #[test]
+    fn test_gtf_writer_comments_and_origin() {
+        let transcriptome = two_inputs();
+        let out = write_gtf(&transcriptome, false);
+        let expected = vec![
+            "##description: first",
+            "##species: human",
+            "# novel",
+            "chr1\ttest\tgene\t100\t200\t.\t+\t.\tgene_id \"g1\";",
+            "chr1\ttest\ttranscript\t100\t200\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";",
+            "chr1\ttest\tgene\t500\t600\t.\t+\t.\tgene_id \"g2\";",
+            "chr1\ttest\ttranscript\t500\t600\t.\t+\t.\tgene_id \"g2\"; transcript_id \"t2\";",
+            "#!genome-build b",
+            "chr2\ttest\tgene\t1\t50\t.\t+\t.\tgene_id \"g3\";",
+            "chr2\ttest\ttranscript\t1\t50\t.\t+\t.\tgene_id \"g3\"; transcript_id \"t3\";",
+            "# end of b",
+        ];
+        assert_eq!(out, expected);
+
+        let mut writer = GtfWriter::new(Vec::new());
+        writer.set_input(Some(1));
+        writer.set_origin_label(Some("source_file"));
+        writer.write(&transcriptome).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(out.lines().collect::<Vec<_>>(), vec![
+            "#!genome-build b",
+            "chr2\ttest\tgene\t1\t50\t.\t+\t.\tgene_id \"g3\"; source_file \"b.gtf\";",
+            "chr2\ttest\ttranscript\t1\t50\t.\t+\t.\tgene_id \"g3\"; transcript_id \"t3\"; source_file \"b.gtf\";",
+            "# end of b",
+        ]);
+    }
+
+    #[test]
+    fn test_gff3_writer_comments_and_origin() {
+        let transcriptome = two_inputs();
+        let mut writer = Gff3Writer::new(Vec::new());
+        writer.set_origin_label(Some("source_file"));
+        writer.write(&transcriptome).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        let out: Vec<&str> = out.lines().collect();
+        assert_eq!(out[..5], ["##gff-version 3", "##description: first", "##sequence-region chr1 1 600", "##sequence-region chr2 1 50", "##species: human"]);
+        assert_eq!(out[5], "# novel");
+        assert_eq!(out[6], "chr1\ttest\tgene\t100\t200\t.\t+\t.\tID=g1;gene_id=g1;source_file=a.gtf");
+        assert_eq!(out[8..11], ["###", "chr1\ttest\tgene\t500\t600\t.\t+\t.\tID=g2;gene_id=g2;source_file=a.gtf", "chr1\ttest\ttranscript\t500\t600\t.\t+\t.\tID=t2;Parent=g2;gene_id=g2;transcript_id=t2;source_file=a.gtf"]);
+        assert_eq!(out[11..13], ["###", "#!genome-build b"]);
+        assert_eq!(out[15], "# end of b");
+        assert_eq!(out.len(), 16);
+
+        let mut writer = Gff3Writer::new(Vec::new());
+        writer.set_input(Some(0));
+        writer.write(&transcriptome).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert!(out.lines().all(|line| !line.starts_with("chr2") && line != "# end of b"));
+        assert_eq!(out.lines().filter(|line| line.starts_with("chr1")).count(), 4);
+    }
+
+    #[test]
+    fn test_writers_parents_from_other_inputs() {
+        let genes = "chr1\ttest\tgene\t1\t100\t.\t+\t.\tID=g1\n";
+        let transcripts = "chr1\ttest\tmRNA\t1\t100\t.\t+\t.\tID=t1;Parent=g1\n\
+                           chr1\ttest\texon\t1\t100\t.\t+\t.\tParent=t1\n";
+        let mut reader = TReader::default();
+        reader.add_reader("genes.gff", genes.as_bytes()).unwrap();
+        reader.add_reader("transcripts.gff", transcripts.as_bytes()).unwrap();
+        let mut transcriptome = Transcriptome::new();
+        transcriptome.add_from_treader(&mut reader).unwrap();
+        transcriptome.finalize().unwrap();
+
+        // the transcript of the second input is written at the top level with the IDs of its gene
+        let mut writer = GtfWriter::new(Vec::new());
+        writer.set_input(Some(1));
+        writer.write(&transcriptome).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(out.lines().collect::<Vec<_>>(), vec![
+            "chr1\ttest\tmRNA\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";",
+            "chr1\ttest\texon\t1\t100\t.\t+\t.\tgene_id \"g1\"; transcript_id \"t1\";",
+        ]);
+
+        // the gene is not written, so neither is the reference to it
+        let mut writer = Gff3Writer::new(Vec::new());
+        writer.set_input(Some(1));
+        writer.write(&transcriptome).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        let records: Vec<&str> = out.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(records, vec!["chr1\ttest\tmRNA\t1\t100\t.\t+\t.\tID=t1", "chr1\ttest\texon\t1\t100\t.\t+\t.\tID=t1.exon1;Parent=t1"]);
+    }
+
+    #[test]
+    fn test_gff3_writer() {
+        let lines = "chr1\ttest\ttranscript\t100\t400\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\"; gene_name \"A,B\"; level 2; tag \"basic\"; tag \"CCDS\";\n\
+                     chr1\ttest\texon\t100\t200\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\"; exon_number 2;\n\
+                     chr1\ttest\texon\t300\t400\t.\t-\t.\tgene_id \"g1\"; transcript_id \"t1\"; exon_number 1;\n\
+                     chr1\ttest\tCDS\t300\t350\t.\t-\t0\tgene_id \"g1\"; transcript_id \"t1\"; exon_number 1;\n\
+                     chr1\ttest\ttranscript\t10\t50\t.\t+\t.\tgene_id \"g0\"; transcript_id \"g0\";\n\
+                     chr1\ttest\texon\t10\t50\t.\t+\t.\tgene_id \"g0\"; transcript_id \"g0\";\n\
+                     chr2\ttest\ttranscript\t10\t50\t.\t+\t.\tgene_id \"g2\"; transcript_id \"t2\";\n\
+                     chr2\ttest\texon\t10\t50\t.\t+\t.\tgene_id \"g2\"; transcript_id \"t2\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+
+        let mut writer = Gff3Writer::new(Vec::new());
+        writer.set_dialect(Some(Dialect::GFF3));
+        writer.write(&transcriptome).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        let expected = vec![
+            "##gff-version 3",
+            "#!gtf-unquoted level exon_number",
+            "##sequence-region chr1 1 400",
+            "##sequence-region chr2 1 50",
+            "chr1\ttest\tgene\t10\t50\t.\t+\t.\tID=g0;gene_id=g0",
+            "chr1\ttest\ttranscript\t10\t50\t.\t+\t.\tID=g0_2;Parent=g0;gene_id=g0;transcript_id=g0",
+            "chr1\ttest\texon\t10\t50\t.\t+\t.\tID=g0_2.exon1;Parent=g0_2;gene_id=g0;transcript_id=g0",
+            "###",
+            "chr1\ttest\tgene\t100\t400\t.\t-\t.\tID=g1;gene_id=g1;gene_name=A%2CB;level=2;tag=basic,CCDS",
+            "chr1\ttest\ttranscript\t100\t400\t.\t-\t.\tID=t1;Parent=g1;gene_id=g1;transcript_id=t1;gene_name=A%2CB;level=2;tag=basic,CCDS",
+            "chr1\ttest\texon\t300\t400\t.\t-\t.\tID=t1.exon1;Parent=t1;gene_id=g1;transcript_id=t1;exon_number=1",
+            "chr1\ttest\tCDS\t300\t350\t.\t-\t0\tID=t1.CDS1;Parent=t1;gene_id=g1;transcript_id=t1;exon_number=1",
+            "chr1\ttest\texon\t100\t200\t.\t-\t.\tID=t1.exon2;Parent=t1;gene_id=g1;transcript_id=t1;exon_number=2",
+            "###",
+            "chr2\ttest\tgene\t10\t50\t.\t+\t.\tID=g2;gene_id=g2",
+            "chr2\ttest\ttranscript\t10\t50\t.\t+\t.\tID=t2;Parent=g2;gene_id=g2;transcript_id=t2",
+            "chr2\ttest\texon\t10\t50\t.\t+\t.\tID=t2.exon1;Parent=t2;gene_id=g2;transcript_id=t2",
+        ];
+        assert_eq!(out.lines().collect::<Vec<_>>(), expected);
+
+        // converting back to GTF gives the same records as writing the GTF directly
+        let mut reread = Transcriptome::from_reader(out.as_bytes()).unwrap();
+        reread.finalize().unwrap();
+        assert_eq!(reread.objects().len(), transcriptome.objects().len());
+        assert_eq!(write_gtf(&reread, true), write_gtf(&transcriptome, true));
+        // repeated keys and unquoted values included
+        let gtf = write_gtf(&reread, false);
+        assert_eq!(gtf, write_gtf(&transcriptome, false));
+        assert!(gtf.iter().any(|line| line.ends_with("gene_name \"A,B\"; level 2; tag \"basic\"; tag \"CCDS\";")));
+        assert!(gtf.iter().any(|line| line.ends_with("transcript_id \"t1\"; exon_number 2;")));
+    }
+
+    #[test]
+    fn test_gff3_writer_ids() {
+        // CDS segments sharing an ID are one feature
+        let lines = "##gff-version 3\n\
+                     chr1\ttest\tmRNA\t100\t400\t.\t+\t.\tID=t1\n\
+                     chr1\ttest\tCDS\t100\t200\t.\t+\t0\tID=cds1;Parent=t1\n\
+                     chr1\ttest\tCDS\t300\t400\t.\t+\t2\tID=cds1;Parent=t1\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+        let mut writer = Gff3Writer::new(Vec::new());
+        writer.write(&transcriptome).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(out.lines().filter(|line| line.ends_with("\tID=cds1;Parent=t1")).count(), 2);
+
+        // a renamed duplicate does not take an ID of another record
+        let lines = "chr1\ttest\ttranscript\t10\t50\t.\t+\t.\tgene_id \"g0\"; transcript_id \"g0\";\n\
+                     chr1\ttest\ttranscript\t100\t200\t.\t+\t.\tgene_id \"g5\"; transcript_id \"g0_2\";\n";
+        let mut transcriptome = Transcriptome::from_reader(lines.as_bytes()).unwrap();
+        transcriptome.finalize().unwrap();
+        let mut writer = Gff3Writer::new(Vec::new());
+        writer.write(&transcriptome).unwrap();
+        let out = String::from_utf8(writer.into_inner()).unwrap();
+        let ids: Vec<&str> = out
+            .lines()
+            .filter(|line| !line.starts_with('#'))
+            .map(|line| line.split('\t').nth(8).unwrap().split(';').next().unwrap())
+            .collect();
+        assert_eq!(ids, vec!["ID=g0", "ID=g0_3", "ID=g5", "ID=g0_2"]);
+    }
+}
@@ -3,4 +3,24 @@ use bio::utils::Interval;
 
 #[test]
 fn test_main() {
+}
+
+#[test]
+fn test_complete_hierarchy() {
+    // data/test.gtf has transcript and exon lines but no gene lines
+    let mut transcriptome = Transcriptome::from_file("data/test.gtf").unwrap();
+    let orphans = transcriptome.finalize().unwrap();
+    assert!(orphans.is_empty());
+
+    for obj in transcriptome.objects() {
+        match obj.get_type() {
+            Types::Gene => assert!(!obj.children().is_empty()),
+            Types::Transcript => {
+                let gene = transcriptome.get(obj.parent().unwrap()).unwrap();
+                assert_eq!(gene.get_type(), Types::Gene);
+                assert_eq!(gene.get_attr("gene_id"), obj.get_attr("gene_id"));
+            },
+            _ => assert_eq!(transcriptome.get(obj.parent().unwrap()).unwrap().get_type(), Types::Transcript),
+        }
+    }
 }
\ No newline at end of file